@@ -0,0 +1,222 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use clap::{Args, Subcommand, ValueHint};
+use sha2::{Digest, Sha256};
+
+use cuc::mhex;
+
+#[derive(Debug, Args)]
+#[clap(about = "Manage the disk cache of the dynamic completions run by 'cuc complete'")]
+pub struct Cache {
+    #[command(subcommand)]
+    pub command: CacheCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommands {
+    Clear(Clear),
+    Stats(Stats),
+}
+
+#[derive(Debug, Args)]
+pub struct CacheDir {
+    #[arg(
+        long = "cache-dir",
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "Directory of the cache, the completions being cached in its complete subdirectory. Defaults to %LOCALAPPDATA%\\cuc\\cache on windows, else $XDG_CACHE_HOME/cuc i.e. ~/.cache/cuc."
+    )]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Remove the cached completions")]
+pub struct Clear {
+    #[command(flatten)]
+    pub dir: CacheDir,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Report the number, size and age of the cached completions, and how many expired")]
+pub struct Stats {
+    #[command(flatten)]
+    pub dir: CacheDir,
+}
+
+/// The outputs of the completions, a file per key in the cache dir. A file starts with the ttl
+/// it was cached for, in seconds, on its own line.
+pub struct CompletionCache {
+    dir: PathBuf,
+}
+
+impl CacheCommands {
+    pub fn run(self) -> anyhow::Result<()> {
+        match self {
+            CacheCommands::Clear(cmd) => cmd.run(),
+            CacheCommands::Stats(cmd) => cmd.run(),
+        }
+    }
+}
+
+impl Cache {
+    pub fn run(self) -> anyhow::Result<()> {
+        self.command.run()
+    }
+}
+
+impl CacheDir {
+    pub fn open(&self) -> anyhow::Result<CompletionCache> {
+        let dir = match self.dir {
+            Some(ref dir) => dir.clone(),
+            None => default_cache_dir()?,
+        };
+        Ok(CompletionCache {
+            dir: dir.join("complete"),
+        })
+    }
+}
+
+impl Clear {
+    pub fn run(self) -> anyhow::Result<()> {
+        let cache = self.dir.open()?;
+        let entries = cache.entries()?;
+        for (path, _) in entries.iter() {
+            fs::remove_file(path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        println!(
+            "Removed {} cached completions from {}",
+            entries.len(),
+            cache.dir.display()
+        );
+        Ok(())
+    }
+}
+
+impl Stats {
+    pub fn run(self) -> anyhow::Result<()> {
+        let cache = self.dir.open()?;
+        let entries = cache.entries()?;
+        let size: u64 = entries.iter().map(|(_, meta)| meta.len()).sum();
+        println!("{}", cache.dir.display());
+        let expired = entries
+            .iter()
+            .filter(|(path, meta)| CompletionCache::is_expired(path, meta))
+            .count();
+        println!("Entries: {}", entries.len());
+        println!("Expired: {}", expired);
+        println!("Size: {} bytes", size);
+        let oldest = entries
+            .iter()
+            .filter_map(|(_, meta)| meta.modified().ok()?.elapsed().ok())
+            .max();
+        if let Some(oldest) = oldest {
+            println!("Oldest: {}s ago", oldest.as_secs());
+        }
+        Ok(())
+    }
+}
+
+impl CompletionCache {
+    /// Hashes the parts into a key, each part being delimited so that they can't run together.
+    pub fn key<I, P>(parts: I) -> String
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            let part = part.as_ref();
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        mhex::encode(hasher.finalize())
+    }
+
+    /// The cached output, unless it's missing or older than the ttl or the one it was cached for.
+    /// An expired one is removed, so that they don't pile up.
+    pub fn get(&self, key: &str, ttl: Duration) -> Option<Vec<u8>> {
+        let path = self.dir.join(key);
+        let age = path.metadata().ok()?.modified().ok()?.elapsed().ok()?;
+        let contents = fs::read(&path).ok()?;
+        match Self::split_entry(&contents) {
+            Some((cached_ttl, output)) if age < ttl.min(cached_ttl) => Some(output.to_vec()),
+            _ => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, output: &[u8], ttl: Duration) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        // Written aside then renamed, so that a concurrent completion doesn't read it halfway
+        let path = self.dir.join(key);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut contents = format!("{}\n", ttl.as_secs()).into_bytes();
+        contents.extend_from_slice(output);
+        fs::write(&partial, contents)
+            .with_context(|| format!("failed to write {}", partial.display()))?;
+        fs::rename(&partial, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Whether the cached output is older than the ttl it was cached for, or isn't an entry.
+    fn is_expired(path: &Path, meta: &fs::Metadata) -> bool {
+        let Some(age) = meta.modified().ok().and_then(|time| time.elapsed().ok()) else {
+            return false;
+        };
+        // The ttl is on the first line, so the output doesn't need to be read
+        let mut line = String::new();
+        match fs::File::open(path).map(|file| BufReader::new(file).read_line(&mut line)) {
+            Ok(Ok(_)) => Self::split_entry(line.as_bytes()).is_none_or(|(ttl, _)| age >= ttl),
+            _ => false,
+        }
+    }
+
+    /// Splits the ttl the output was cached for from the output.
+    fn split_entry(contents: &[u8]) -> Option<(Duration, &[u8])> {
+        let end = contents.iter().position(|&b| b == b'\n')?;
+        let ttl = std::str::from_utf8(&contents[..end]).ok()?.parse().ok()?;
+        Some((Duration::from_secs(ttl), &contents[end + 1..]))
+    }
+
+    /// The cached outputs, along with their metadata.
+    fn entries(&self) -> anyhow::Result<Vec<(PathBuf, fs::Metadata)>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut entries = vec![];
+        let read_dir = fs::read_dir(&self.dir)
+            .with_context(|| format!("failed to read {}", self.dir.display()))?;
+        for entry in read_dir {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_file() {
+                entries.push((entry.path(), meta));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn default_cache_dir() -> anyhow::Result<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let dir = if cfg!(windows) {
+        env_dir("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("cuc").join("cache"))
+    } else {
+        env_dir("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env_dir("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|dir| dir.join("cuc"))
+    };
+    dir.context("failed to find the cache dir! Try again with the cache-dir flag")
+}
@@ -1,7 +1,13 @@
 use clap::Args;
-use std::process::{Command, Stdio, exit};
+use std::{
+    io::Write,
+    process::{Command, Stdio, exit},
+    time::Duration,
+};
 
-use crate::mbase64;
+use cuc::{mbase64, mhex};
+
+use super::cache::{CacheDir, CompletionCache};
 
 #[derive(Debug, Args)]
 #[clap(about = "Get completions by running the specified command as args")]
@@ -12,8 +18,20 @@ pub struct Complete {
     )]
     pub current: usize,
 
-    #[arg(long, help = "The current line in its entirety.")]
-    pub line: String,
+    #[arg(
+        long,
+        required_unless_present = "line_hex",
+        help = "The current line in its entirety."
+    )]
+    pub line: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "line",
+        help = "The current line in its entirety, hex encoded.",
+        long_help = "The current line in its entirety, hex encoded. Used by the generated scripts, as the line can contain quotes and characters special to cmd.exe."
+    )]
+    pub line_hex: Option<String>,
 
     #[arg(
         short,
@@ -23,6 +41,24 @@ pub struct Complete {
     )]
     pub shell: String,
 
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "Reuse the output cached on disk for that many seconds, for the same script, cwd and cache env vars."
+    )]
+    pub cache_ttl: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        requires = "cache_ttl",
+        help = "Env var whose value the output depends on, so that it's cached per value. Can be repeated."
+    )]
+    pub cache_env: Vec<String>,
+
+    #[command(flatten)]
+    pub cache_dir: CacheDir,
+
     #[arg(
         last = true,
         help = "List of args to run and output completions to stdout per line.",
@@ -33,10 +69,17 @@ pub struct Complete {
 
 impl Complete {
     pub fn run(self) -> anyhow::Result<()> {
-        let mut words = winsplit::split(&self.line);
+        let line = match self.line_hex {
+            Some(ref line_hex) => mhex::decode(line_hex)?,
+            None => self.line.clone().unwrap_or_default(),
+        };
+        let mut words = winsplit::split(&line);
         let run_template = mbase64::decode(&self.args[0])?;
         let run_script = self.render_run(&run_template, &mut words)?;
-        self.complete(&run_script)?;
+        match self.cache_ttl {
+            Some(ttl) => self.complete_cached(&run_script, Duration::from_secs(ttl))?,
+            None => self.complete(&run_script)?,
+        }
         Ok(())
     }
 
@@ -64,6 +107,40 @@ impl Complete {
         Ok(tera::Tera::one_off(run_template, &context, true)?)
     }
 
+    /// Completes from the cached output if fresh, else caches the output of a successful run.
+    fn complete_cached(&self, run: &str, ttl: Duration) -> anyhow::Result<()> {
+        let cache = self.cache_dir.open()?;
+        let cwd = std::env::current_dir()?;
+        let mut parts = vec![
+            run.as_bytes().to_vec(),
+            self.shell.as_bytes().to_vec(),
+            cwd.to_string_lossy().as_bytes().to_vec(),
+        ];
+        for name in self.cache_env.iter() {
+            let value = std::env::var_os(name).unwrap_or_default();
+            parts.push(format!("{}={}", name, value.to_string_lossy()).into_bytes());
+        }
+        let key = CompletionCache::key(parts);
+        if let Some(output) = cache.get(&key, ttl) {
+            std::io::stdout().write_all(&output)?;
+            return Ok(());
+        }
+
+        let output = Command::new(&self.shell)
+            .arg("-c")
+            .arg(run)
+            .stderr(Stdio::inherit())
+            .output()?;
+        let mut stdout = std::io::stdout();
+        stdout.write_all(&output.stdout)?;
+        stdout.flush()?;
+        if output.status.success() {
+            // The completions were output anyway, a cache that can't be written is only slower
+            let _ = cache.put(&key, &output.stdout, ttl);
+        }
+        exit(output.status.code().unwrap_or(1));
+    }
+
     fn complete<S>(&self, run: S) -> anyhow::Result<()>
     where
         S: AsRef<str>,
@@ -0,0 +1,238 @@
+use anyhow::Context;
+use clap::{
+    Args, ValueHint,
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
+};
+use std::io::BufWriter;
+use std::{
+    fs::{self, OpenOptions},
+    path::PathBuf,
+};
+
+use cuc::{
+    backend::{Completor, GenerateOptions, Registry, write_io},
+    graph::{CompletionGraph, Hidden},
+    usage::CachePolicy,
+};
+
+use crate::spec::UsageSpecExt;
+
+#[derive(Debug, Args)]
+#[clap(about = "Generate completions (clink argmatcher by default) from the usage spec")]
+pub struct Generate {
+    #[arg(help = "Path to usage.spec.kdl. Reads file content from stdin if none provided.", value_hint = ValueHint::FilePath)]
+    pub usage_spec: Option<PathBuf>,
+
+    #[arg(
+        long = "arg-matcher",
+        help = "List of command names to generate the clink.argmatcher() for. Overrides the name in the usage spec."
+    )]
+    pub arg_matchers: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        value_name = "LUA",
+        value_hint = ValueHint::FilePath,
+        help = "Path to usage_completions.lua to write to, else write to stdout."
+    )]
+    pub out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with = "out",
+        help = "Split the completions into a root script and a file per top-level cmd, written to the dir.",
+        long_help = "Split the completions into a root script and a file per top-level cmd, written to the dir. The root script loads the file of a cmd once it's first completed, so it must be loaded from the dir (e.g. a clink scripts dir) instead of stdin. The functions are kept in a module table as with --module, since the files can't see the locals of the root script."
+    )]
+    pub split_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Generate dynamic completion for args from 'complete' node."
+    )]
+    pub complete: bool,
+
+    #[arg(
+        long,
+        help = "The shell that'll be used to run the completion command."
+    )]
+    pub shell: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "clink",
+        value_parser = target_parser(),
+        help = "The format of the generated completions.",
+        long_help = "The format of the generated completions. Not every target supports every option, the generation fails on those it doesn't."
+    )]
+    pub target: String,
+
+    #[arg(long, help = "Minify the generated completions.")]
+    pub minify: bool,
+
+    #[arg(
+        long,
+        help = "Keep the generated functions in a local module table returned by the script.",
+        long_help = "Keep the generated functions in a local module table returned by the script. Otherwise, the functions of the cmds and flags are global and can be overwritten by the completions of another spec loaded in the same session, while the helpers stay local to the script."
+    )]
+    pub module: bool,
+
+    #[arg(
+        long,
+        help = "Embed the helper modules (i.e. arghelper.lua) in the output, so that it runs without them on package.path."
+    )]
+    pub standalone: bool,
+
+    #[arg(
+        long,
+        default_value = "omit",
+        value_parser = hidden_parser(),
+        help = "How to complete the cmds, flags, args and aliases hidden by the spec."
+    )]
+    pub hidden: Hidden,
+
+    #[arg(
+        long,
+        help = "Complete the arg of long flags after an '=' too, e.g. '--out=<TAB>'.",
+        long_help = "Complete the arg of long flags after an '=' too, e.g. '--out=<TAB>'. A flag can override it with its 'opteq' property."
+    )]
+    pub opteq: bool,
+
+    #[arg(
+        long,
+        help = "Complete the default of an arg as its first match, so that completing an empty arg fills it in."
+    )]
+    pub suggest_defaults: bool,
+
+    #[arg(
+        long,
+        help = "List the cmds, flags and values in the order of the spec, or of the dynamic completions, instead of sorting them.",
+        long_help = "List the cmds, flags and values in the order of the spec, or of the dynamic completions, instead of sorting them. A cmd, arg or complete node can override it with its 'nosort' property."
+    )]
+    pub preserve_order: bool,
+
+    #[arg(
+        long = "async",
+        help = "Run the dynamic completions in the background, so that slow ones don't block the prompt.",
+        long_help = "Run the dynamic completions in the background, so that slow ones don't block the prompt. The matches are filled in once ready, a hint telling they're loading meanwhile. A complete node can override it with its 'async' property."
+    )]
+    pub async_complete: bool,
+
+    #[arg(
+        long = "cache-ttl",
+        value_name = "POLICY",
+        default_value = "off",
+        value_parser = |policy: &str| policy.parse::<CachePolicy>(),
+        help = "Reuse the output of the dynamic completions for the same inputs within the shell: off, session or a number of seconds.",
+        long_help = "Reuse the output of the dynamic completions for the same inputs within the shell: off, session or a number of seconds. A complete node can override it with its 'cache' property."
+    )]
+    pub cache: CachePolicy,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        requires = "complete",
+        help = "Let 'cuc complete' reuse the output of the dynamic completions cached on disk for that many seconds, across shells.",
+        long_help = "Let 'cuc complete' reuse the output of the dynamic completions cached on disk for that many seconds, across shells. It's keyed by the rendered script, the cwd and the env vars of the 'cache_env' property of the complete node, which can override it with its 'disk_cache' property. See 'cuc cache'."
+    )]
+    pub disk_cache_ttl: Option<u64>,
+}
+
+impl Generate {
+    pub fn run(self) -> anyhow::Result<()> {
+        let registry = Registry::default();
+        let Some(backend) = registry.get(&self.target) else {
+            anyhow::bail!("unknown target: {}", self.target);
+        };
+
+        let mut usage_spec = cuc::usage::UsageSpec::load(self.usage_spec.as_ref())?;
+        cuc::usage::UsageSpec::add_default_completes(&mut usage_spec.completes);
+        let mut options = GenerateOptions::default();
+        if self.complete {
+            options.completor = Some(Completor {
+                exe_path: std::env::current_exe()?,
+                shell: self.find_shell()?,
+                cache_ttl: self.disk_cache_ttl,
+            });
+        }
+        options.arg_matchers = self.arg_matchers;
+        options.minify = self.minify;
+        options.module = self.module;
+        options.standalone = self.standalone;
+        options.opteq = self.opteq;
+        options.suggest_defaults = self.suggest_defaults;
+        options.preserve_order = self.preserve_order;
+        options.async_complete = self.async_complete;
+        options.cache = self.cache;
+
+        let graph = CompletionGraph::build_with(&usage_spec, self.hidden);
+        if let Some(dir) = self.split_dir {
+            for file in backend.generate_split(&graph, &options)? {
+                let path = dir.join(&file.path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, file.content)
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+            }
+            return Ok(());
+        }
+        if let Some(out) = self.out {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&out)?;
+            write_io(backend, &graph, &options, BufWriter::new(file))?;
+        } else {
+            write_io(
+                backend,
+                &graph,
+                &options,
+                BufWriter::new(std::io::stdout().lock()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn find_shell(&self) -> anyhow::Result<PathBuf> {
+        if let Some(ref path) = self.shell {
+            return Ok(which::which(path)?.canonicalize()?);
+        }
+
+        let git_bin = which::which("git.exe")?;
+        if let Some(git_bin_dir) = git_bin.parent() {
+            match git_bin_dir.file_name().map(|ostr| ostr.to_str().unwrap()) {
+                Some("cmd") => {
+                    return Ok(which::which(git_bin_dir.join("..\\bin\\bash.exe"))?.canonicalize()?);
+                }
+                Some("bin") => {
+                    return Ok(which::which(git_bin_dir.join("bash.exe"))?.canonicalize()?);
+                }
+                _ => {}
+            };
+        }
+        anyhow::bail!("failed to find bash shell! Try again with inputting the shell flag");
+    }
+}
+
+fn target_parser() -> PossibleValuesParser {
+    let registry = Registry::default();
+    let targets = registry
+        .iter()
+        .map(|backend| PossibleValue::new(backend.name()).help(backend.about()));
+    PossibleValuesParser::new(targets)
+}
+
+fn hidden_parser() -> impl TypedValueParser<Value = Hidden> {
+    PossibleValuesParser::new([
+        PossibleValue::new("omit").help("Leave them out, as if they weren't in the spec"),
+        PossibleValue::new("hide")
+            .help("Keep them typeable and coloured, but out of the match list"),
+        PossibleValue::new("show").help("Complete them like any other"),
+    ])
+    .try_map(|hidden| hidden.parse::<Hidden>())
+}
@@ -1,8 +1,10 @@
 use clap::{Parser, Subcommand};
 
+mod cache;
 mod complete;
 mod generate;
 mod last_modified;
+mod modules;
 mod usage;
 
 #[derive(Debug, Parser)]
@@ -23,18 +25,22 @@ pub struct Cli {
 pub enum Commands {
     Generate(generate::Generate),
     Complete(complete::Complete),
+    Cache(cache::Cache),
     Usage(usage::Usage),
+    Modules(modules::Modules),
     LastModified(last_modified::LastModified),
 }
 
 impl Commands {
     pub fn run(self) -> anyhow::Result<()> {
-        let result = match self {
+        match self {
             Commands::Generate(cmd) => cmd.run()?,
             Commands::Complete(cmd) => cmd.run()?,
+            Commands::Cache(cmd) => cmd.run()?,
             Commands::Usage(cmd) => cmd.run()?,
+            Commands::Modules(cmd) => cmd.run()?,
             Commands::LastModified(cmd) => cmd.run()?,
         };
-        Ok(result)
+        Ok(())
     }
 }
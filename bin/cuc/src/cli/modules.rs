@@ -0,0 +1,229 @@
+use std::{
+    cmp::Ordering,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Args, Subcommand, ValueHint};
+
+use cuc::backend::clink::{ARGHELPER, ARGHELPER_VERSION};
+
+#[derive(Debug, Args)]
+#[clap(about = "Manage the lua modules required by the generated clink scripts")]
+pub struct Modules {
+    #[command(subcommand)]
+    pub command: ModulesCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ModulesCommands {
+    Install(Install),
+    Status(Status),
+}
+
+#[derive(Debug, Args)]
+pub struct ModulesDir {
+    #[arg(
+        long,
+        value_hint = ValueHint::DirPath,
+        help = "Directory of the modules. Defaults to the modules dir of the clink profile i.e. %CLINK_PROFILE%\\modules."
+    )]
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Write the bundled arghelper.lua into the modules dir")]
+pub struct Install {
+    #[command(flatten)]
+    pub dir: ModulesDir,
+
+    #[arg(
+        long,
+        help = "Overwrite the installed copy even if it's newer or was modified."
+    )]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Report whether the installed arghelper.lua is the one expected by this cuc")]
+pub struct Status {
+    #[command(flatten)]
+    pub dir: ModulesDir,
+}
+
+/// The bundled modules, with the version of cuc-lib they're bundled with.
+const MODULES: &[(&str, &str, &str)] = &[("arghelper.lua", ARGHELPER, ARGHELPER_VERSION)];
+
+/// Records the version of each module written by cuc, a `name=version` per line.
+const MANIFEST: &str = ".cuc-modules";
+
+/// State of an installed module, compared with the bundled one.
+#[derive(Debug, PartialEq, Eq)]
+enum ModuleState {
+    Missing,
+    UpToDate,
+    /// Written by an older cuc.
+    Older(String),
+    /// Written by a newer cuc, so installing would downgrade it.
+    Newer(String),
+    /// Modified since written by cuc, or not written by cuc at all.
+    Modified,
+}
+
+impl ModulesCommands {
+    pub fn run(self) -> anyhow::Result<()> {
+        match self {
+            ModulesCommands::Install(cmd) => cmd.run(),
+            ModulesCommands::Status(cmd) => cmd.run(),
+        }
+    }
+}
+
+impl Modules {
+    pub fn run(self) -> anyhow::Result<()> {
+        self.command.run()
+    }
+}
+
+impl ModulesDir {
+    fn resolve(&self) -> anyhow::Result<PathBuf> {
+        if let Some(ref dir) = self.dir {
+            return Ok(dir.clone());
+        }
+        let profile = std::env::var_os("CLINK_PROFILE")
+            .context("CLINK_PROFILE isn't set! Try again with the dir flag")?;
+        Ok(PathBuf::from(profile).join("modules"))
+    }
+}
+
+impl Install {
+    pub fn run(self) -> anyhow::Result<()> {
+        let dir = self.dir.resolve()?;
+        let mut manifest = read_manifest(&dir)?;
+        for (name, content, version) in MODULES {
+            let path = dir.join(name);
+            let recorded = manifest.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+            match module_state(&path, content, version, recorded)? {
+                ModuleState::UpToDate => {
+                    println!("{} is up to date ({})", path.display(), version);
+                }
+                ModuleState::Newer(newer) if !self.force => {
+                    anyhow::bail!(
+                        "{} was written by a newer cuc ({} > {}), refusing to downgrade it. Use --force to overwrite",
+                        path.display(),
+                        newer,
+                        version
+                    );
+                }
+                ModuleState::Modified if !self.force => {
+                    anyhow::bail!(
+                        "{} differs from the one bundled with this cuc ({}). Use --force to overwrite",
+                        path.display(),
+                        version
+                    );
+                }
+                _ => {
+                    fs::create_dir_all(&dir)?;
+                    fs::write(&path, content)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!("Installed {} ({})", path.display(), version);
+                }
+            }
+            manifest.retain(|(n, _)| n != name);
+            manifest.push((name.to_string(), version.to_string()));
+        }
+        write_manifest(&dir, &manifest)
+    }
+}
+
+impl Status {
+    pub fn run(self) -> anyhow::Result<()> {
+        let dir = self.dir.resolve()?;
+        let manifest = read_manifest(&dir)?;
+        let mut expected = true;
+        for (name, content, version) in MODULES {
+            let path = dir.join(name);
+            let recorded = manifest.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+            let state = module_state(&path, content, version, recorded)?;
+            expected &= state == ModuleState::UpToDate;
+            match state {
+                ModuleState::Missing => println!("{}: missing", path.display()),
+                ModuleState::UpToDate => println!("{}: up to date ({})", path.display(), version),
+                ModuleState::Older(older) => {
+                    println!("{}: outdated ({} < {})", path.display(), older, version)
+                }
+                ModuleState::Newer(newer) => {
+                    println!("{}: newer ({} > {})", path.display(), newer, version)
+                }
+                ModuleState::Modified => {
+                    println!(
+                        "{}: differs from the bundled one ({})",
+                        path.display(),
+                        version
+                    )
+                }
+            }
+        }
+        if !expected {
+            anyhow::bail!("the installed modules aren't the ones expected by this cuc");
+        }
+        Ok(())
+    }
+}
+
+fn module_state(
+    path: &Path,
+    content: &str,
+    version: &str,
+    recorded: Option<&String>,
+) -> anyhow::Result<ModuleState> {
+    if !path.exists() {
+        return Ok(ModuleState::Missing);
+    }
+    if let Some(recorded) = recorded
+        && compare_versions(recorded, version) == Ordering::Greater
+    {
+        return Ok(ModuleState::Newer(recorded.clone()));
+    }
+    let installed =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if installed == content {
+        return Ok(ModuleState::UpToDate);
+    }
+    match recorded {
+        Some(recorded) if compare_versions(recorded, version) == Ordering::Less => {
+            Ok(ModuleState::Older(recorded.clone()))
+        }
+        _ => Ok(ModuleState::Modified),
+    }
+}
+
+/// Compares dot separated versions numerically, e.g. `0.10.0` is greater than `0.9.1`.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| -> Vec<u64> { v.split('.').map(|n| n.parse().unwrap_or(0)).collect() };
+    parse(a).cmp(&parse(b))
+}
+
+fn read_manifest(dir: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let path = dir.join(MANIFEST);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let manifest =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(manifest
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, version)| (name.trim().to_string(), version.trim().to_string()))
+        .collect())
+}
+
+fn write_manifest(dir: &Path, manifest: &[(String, String)]) -> anyhow::Result<()> {
+    let path = dir.join(MANIFEST);
+    let manifest: String = manifest
+        .iter()
+        .map(|(name, version)| format!("{}={}\n", name, version))
+        .collect();
+    fs::write(&path, manifest).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -1,15 +1,11 @@
 use kdl::KdlDocument;
 use std::{
-    borrow::BorrowMut,
     collections::HashMap,
     io::{IsTerminal, Read},
     path::{Path, PathBuf},
 };
 
-use cuc::{
-    namespace::NameSpace,
-    usage::{parse_bin, parse_include, parse_name, parse_usage},
-};
+use cuc::usage::{parse_bin, parse_include, parse_name, parse_usage};
 
 pub trait UsageSpecExt
 where
@@ -21,10 +17,6 @@ where
         S: AsRef<str>;
     fn merge(self, other: Self) -> Self;
     fn add_default_completes(completes: &mut HashMap<String, cuc::usage::Complete>);
-    fn add_global_flag_to_cmd(flag: &cuc::usage::Flag, cmd: &mut cuc::usage::Cmd, nm: NameSpace);
-    fn add_global_flags_to_all_subcmds<C>(cmds: &mut [C], nm: NameSpace)
-    where
-        C: BorrowMut<cuc::usage::Cmd>;
 }
 
 pub struct ParsingContext {
@@ -87,8 +79,7 @@ impl UsageSpecExt for cuc::usage::UsageSpec {
                                     path_buf.parent().unwrap().to_path_buf()
                                 }
                             };
-                            let file = parent.join(include_path);
-                            file
+                            parent.join(include_path)
                         }
                         false => include_path.to_path_buf(),
                     };
@@ -100,32 +91,16 @@ impl UsageSpecExt for cuc::usage::UsageSpec {
             let usage = parse_usage(node)?;
             if let Some(usage) = usage {
                 match usage {
-                    cuc::usage::Usage::Flag(flag) if !flag.hide => flags.push(flag),
-                    cuc::usage::Usage::Arg(arg) if !arg.hide => args.push(arg),
-                    cuc::usage::Usage::Cmd(cmd) if !cmd.hide => cmds.push(cmd),
+                    cuc::usage::Usage::Flag(flag) => flags.push(flag),
+                    cuc::usage::Usage::Arg(arg) => args.push(arg),
+                    cuc::usage::Usage::Cmd(cmd) => cmds.push(cmd),
                     cuc::usage::Usage::Complete(complete) => {
                         completes.insert(complete.name.to_lowercase(), complete);
                     }
-                    _ => (),
                 };
             }
         }
 
-        // Adding imposed global flags to its subsequent subcmd, recursively
-        {
-            let nm = NameSpace::root();
-            Self::add_global_flags_to_all_subcmds(&mut cmds, nm.clone());
-
-            for flag in &flags {
-                if !flag.is_global_itself() {
-                    continue;
-                }
-                for cmd in &mut cmds {
-                    Self::add_global_flag_to_cmd(flag, cmd, nm.clone());
-                }
-            }
-        }
-
         let mut usage_spec = cuc::usage::UsageSpec {
             info,
             flags,
@@ -180,36 +155,4 @@ impl UsageSpecExt for cuc::usage::UsageSpec {
         completes.insert("file".into(), cuc::usage::Complete::file_complete());
         completes.insert("dir".into(), cuc::usage::Complete::dir_complete());
     }
-
-    fn add_global_flag_to_cmd(flag: &cuc::usage::Flag, cmd: &mut cuc::usage::Cmd, nm: NameSpace) {
-        if flag.is_global_itself() {
-            let mut flag = flag.clone();
-            if !cmd.flags.contains(&flag) {
-                let global = cuc::usage::GlobalFlag::Imposed(nm);
-                flag.global = global;
-                cmd.flags.push(flag);
-            }
-        }
-    }
-
-    fn add_global_flags_to_all_subcmds<C>(cmds: &mut [C], nm: NameSpace)
-    where
-        C: BorrowMut<cuc::usage::Cmd>,
-    {
-        for cmd in cmds {
-            let cmd: &mut cuc::usage::Cmd = cmd.borrow_mut();
-            let chnm = nm.clone().join(&cmd.name);
-            Self::add_global_flags_to_all_subcmds(&mut cmd.cmds, chnm.clone());
-
-            for flag in &cmd.flags {
-                if !flag.is_global_itself() {
-                    continue;
-                }
-
-                for subcmd in &mut cmd.cmds {
-                    Self::add_global_flag_to_cmd(flag, subcmd, chnm.clone());
-                }
-            }
-        }
-    }
 }
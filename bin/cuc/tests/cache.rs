@@ -0,0 +1,97 @@
+//! Tests of the disk cache of `cuc complete` and of `cuc cache`.
+
+mod common;
+
+use std::fs;
+
+use common::{run_cuc, temp_dir};
+
+#[cfg(unix)]
+#[test]
+fn test_complete_output_is_cached() {
+    let dir = temp_dir("cache-complete");
+    let dir_str = dir.to_str().unwrap();
+    let count = dir.join("count");
+    let script = format!("echo run >> '{}'; echo dev", count.display());
+    let script = cuc::mbase64::encode(&script);
+    let complete = |ttl: &str| {
+        run_cuc(&[
+            "complete",
+            "--current",
+            "1",
+            "--line",
+            "cmd ",
+            "--shell",
+            "sh",
+            "--cache-ttl",
+            ttl,
+            "--cache-dir",
+            dir_str,
+            "--",
+            &script,
+        ])
+    };
+    let runs = || fs::read_to_string(&count).unwrap().lines().count();
+
+    assert_eq!(complete("60"), (true, "dev\n".into(), String::new()));
+    assert_eq!(complete("60"), (true, "dev\n".into(), String::new()));
+    assert_eq!(runs(), 1);
+    // Run again once older than the ttl
+    assert!(complete("0").0);
+    assert_eq!(runs(), 2);
+
+    let stats = || run_cuc(&["cache", "stats", "--cache-dir", dir_str]);
+    let (ok, stdout, _) = stats();
+    assert!(ok);
+    assert!(stdout.contains("Entries: 1"), "{}", stdout);
+    assert!(run_cuc(&["cache", "clear", "--cache-dir", dir_str]).0);
+    let (_, stdout, _) = stats();
+    assert!(stdout.contains("Entries: 0"), "{}", stdout);
+    assert!(complete("60").0);
+    assert_eq!(runs(), 3);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_expired_outputs_are_removed() {
+    let dir = temp_dir("cache-expired");
+    let dir_str = dir.to_str().unwrap();
+    let fail = dir.join("fail");
+    let script = format!("[ -e '{}' ] && exit 1; echo dev", fail.display());
+    let script = cuc::mbase64::encode(&script);
+    let complete = |ttl: &str| {
+        run_cuc(&[
+            "complete",
+            "--current",
+            "1",
+            "--line",
+            "cmd ",
+            "--shell",
+            "sh",
+            "--cache-ttl",
+            ttl,
+            "--cache-dir",
+            dir_str,
+            "--",
+            &script,
+        ])
+    };
+    let stats = || run_cuc(&["cache", "stats", "--cache-dir", dir_str]).1;
+
+    // Expired as soon as it's cached
+    assert!(complete("0").0);
+    let stdout = stats();
+    assert!(stdout.contains("Entries: 1"), "{}", stdout);
+    assert!(stdout.contains("Expired: 1"), "{}", stdout);
+    assert!(complete("60").0);
+    let stdout = stats();
+    assert!(stdout.contains("Entries: 1"), "{}", stdout);
+    assert!(stdout.contains("Expired: 0"), "{}", stdout);
+
+    // Removed once read expired, even if running it again fails
+    fs::write(&fail, "").unwrap();
+    assert!(!complete("0").0);
+    let stdout = stats();
+    assert!(stdout.contains("Entries: 0"), "{}", stdout);
+    assert!(stdout.contains("Expired: 0"), "{}", stdout);
+}
@@ -0,0 +1,766 @@
+//! Loads the generated clink scripts in a lua state, with the clink api stubbed.
+
+mod common;
+
+use common::{clink_lua, fixture_specs, generate, lua_globals};
+
+#[test]
+fn test_modules_load_together() {
+    let lua = clink_lua();
+    let globals = lua_globals(&lua);
+    for spec in fixture_specs() {
+        let script = generate(&spec, &["--module"]);
+        let name = spec.display().to_string();
+        let module: mlua::Table = lua.load(&script).set_name(name).eval().unwrap();
+        assert!(module.contains_key("loop_until").unwrap());
+    }
+    assert_eq!(lua_globals(&lua), globals, "the modules leak globals");
+
+    let argmatchers: mlua::Table = lua.load("clink.argmatchers").eval().unwrap();
+    let bins: Vec<String> = argmatchers
+        .sequence_values::<mlua::Table>()
+        .filter_map(|matcher| {
+            matcher
+                .unwrap()
+                .get::<_, mlua::Table>("names")
+                .unwrap()
+                .get(1)
+                .ok()
+        })
+        .collect();
+    assert_eq!(
+        bins,
+        vec!["basic", "complete", "hidden", "names", "nested", "validate"]
+    );
+}
+
+#[test]
+fn test_functions_are_global_by_default() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("basic.kdl");
+    let globals = lua_globals(&lua);
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let added: Vec<String> = lua_globals(&lua)
+        .into_iter()
+        .filter(|name| !globals.contains(name))
+        .collect();
+    // The helpers stay local, so that another script can't overwrite them
+    assert_eq!(added, vec!["_flag_color", "_flag_include", "_flag_pair"]);
+}
+
+#[test]
+fn test_standalone_runs_without_arghelper() {
+    let lua = clink_lua();
+    lua.load("package.preload.arghelper = nil; package.loaded.arghelper = nil")
+        .exec()
+        .unwrap();
+    for spec in fixture_specs() {
+        lua.load(generate(&spec, &["--standalone", "--module"]))
+            .exec()
+            .unwrap();
+    }
+    let arghelper: mlua::Table = lua.load("package.loaded.arghelper").eval().unwrap();
+    assert!(arghelper.contains_key("make_arg_hider_func").unwrap());
+}
+
+#[test]
+fn test_matchers_are_built_lazily_once() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("nested.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let count = || lua.load("#clink.argmatchers").eval::<usize>().unwrap();
+    // Only the root is built on load
+    assert_eq!(count(), 1);
+
+    lua.load("init_argmatchers()").exec().unwrap();
+    // The root, remote, remote add, remote remove, exec, version and the args of --cwd and
+    // --upstream, each built once although linked by aliases and by the inherited global flags.
+    assert_eq!(count(), 8);
+}
+
+#[test]
+fn test_split_loads_cmd_files_on_demand() {
+    let dir = common::temp_dir("split");
+    let spec = common::tests_dir().join("fixtures").join("nested.kdl");
+    let (success, _, stderr) = common::run_cuc(&[
+        "generate",
+        spec.to_str().unwrap(),
+        "--module",
+        "--split-dir",
+        dir.to_str().unwrap(),
+    ]);
+    assert!(success, "{}", stderr);
+
+    let lua = clink_lua();
+    let globals = lua_globals(&lua);
+    let dofile: mlua::Function = lua.globals().get("dofile").unwrap();
+    dofile
+        .call::<_, ()>(dir.join("nested.lua").to_str().unwrap())
+        .unwrap();
+    assert_eq!(lua_globals(&lua), globals, "the split module leaks globals");
+
+    lua.load(
+        r#"
+        loaded = {}
+        local real_dofile = dofile
+        function dofile(path)
+            table.insert(loaded, path:match("[^\\/]+$"))
+            return real_dofile(path)
+        end
+        "#,
+    )
+    .exec()
+    .unwrap();
+    let loaded = || {
+        lua.load("table.concat(loaded, ' ')")
+            .eval::<String>()
+            .unwrap()
+    };
+    // Building the root only creates the argmatchers of its cmds
+    lua.load("local root = clink.argmatchers[1]; root.delayinit(root); root.delayinit = nil")
+        .exec()
+        .unwrap();
+    assert_eq!(loaded(), "");
+
+    lua.load("init_argmatchers()").exec().unwrap();
+    assert_eq!(loaded(), "remote.lua exec.lua version.lua");
+    let count = lua.load("#clink.argmatchers").eval::<usize>().unwrap();
+    assert_eq!(count, 8);
+}
+
+#[test]
+fn test_hidden_are_hidden_from_the_matches() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("hidden.kdl");
+    lua.load(generate(&spec, &["--hidden", "hide"]))
+        .exec()
+        .unwrap();
+    let hidden: Vec<String> = lua
+        .load(
+            r#"
+            local root = clink.argmatchers[1]
+            root.delayinit(root)
+            local hidden = {}
+            for _, call in ipairs(root.calls) do
+                for _, entry in ipairs(call.args[1] or {}) do
+                    if type(entry) == "table" and entry.hide then
+                        table.insert(hidden, tostring(entry[1]))
+                    end
+                end
+            end
+            return hidden
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(hidden, vec!["--loud", "--internal", "compile", "debug"]);
+}
+
+#[test]
+fn test_omitted_args_keep_their_position() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("hidden.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let positions: Vec<String> = lua
+        .load(
+            r#"
+            init_argmatchers()
+            local positions = {}
+            for _, call in ipairs(_cmd_build().calls) do
+                if call.method == "_addexarg" then
+                    local matches = {}
+                    for _, entry in ipairs(call.args[1]) do
+                        table.insert(matches, entry)
+                    end
+                    table.insert(positions, table.concat(matches, " "))
+                end
+            end
+            return positions
+            "#,
+        )
+        .eval()
+        .unwrap();
+    // [profile] is left out of the matches, but [jobs] is still completed as the 2nd arg
+    assert_eq!(positions, vec!["debug release", "", "1 2 4"]);
+}
+
+#[test]
+fn test_long_flags_take_their_arg_after_eq() {
+    let opteq_flags = |args: &[&str]| -> Vec<String> {
+        let lua = clink_lua();
+        let spec = common::tests_dir().join("fixtures").join("nested.kdl");
+        lua.load(generate(&spec, args)).exec().unwrap();
+        lua.load(
+            r#"
+            init_argmatchers()
+            local flags = {}
+            -- The global flags are nested tables of entries
+            local function visit(entries)
+                for _, entry in ipairs(entries) do
+                    if type(entry) == "table" then
+                        if entry.opteq then
+                            flags[tostring(entry[1])] = true
+                        end
+                        visit(entry)
+                    end
+                end
+            end
+            for _, matcher in ipairs(clink.argmatchers) do
+                for _, call in ipairs(matcher.calls) do
+                    if call.method == "_addexflags" then
+                        visit(call.args[1])
+                    end
+                end
+            end
+            local names = {}
+            for name in pairs(flags) do
+                table.insert(names, name)
+            end
+            table.sort(names)
+            return names
+            "#,
+        )
+        .eval()
+        .unwrap()
+    };
+    // Neither the short nor the argless flags
+    assert_eq!(opteq_flags(&["--opteq"]), vec!["--cwd", "--upstream"]);
+    assert!(opteq_flags(&[]).is_empty());
+}
+
+#[test]
+fn test_used_flags_are_hidden_unless_repeatable() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("basic.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let matches: Vec<String> = lua
+        .load(
+            r#"
+            init_argmatchers()
+            local line = "basic --verbose --include=src "
+            return complete_flags(clink.argmatchers[1], line, { "--verbose", "--include=src" })
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(matches, vec!["--color", "-I", "--include", "--pair"]);
+}
+
+#[test]
+fn test_used_global_flags_are_shared_by_the_subcmds() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("nested.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    lua.load(
+        r#"
+        init_argmatchers()
+        -- Parses the flags of the cmds as clink does, each argmatcher with its own user_data
+        function parse(line, words)
+            for _, word in ipairs(words) do
+                clink_flags(word[1]).onarg(0, word[2], 2, line_state(line), {})
+            end
+        end
+        "#,
+    )
+    .exec()
+    .unwrap();
+    let unused: Vec<String> = lua
+        .load(
+            r#"
+            local line = "nested --debug remote "
+            complete_flags(clink.argmatchers[1], line, { "--debug" })
+            return complete_flags(_cmd_remote(), line, {})
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(
+        unused,
+        vec!["-C", "--cwd", "-n", "--dry-run", "-u", "--upstream"]
+    );
+    // Listed until used on the line
+    let unused: Vec<String> = lua
+        .load(r#"return complete_flags(_cmd_remote(), "nested remote ", {})"#)
+        .eval()
+        .unwrap();
+    assert_eq!(
+        unused,
+        vec![
+            "-C",
+            "--cwd",
+            "--debug",
+            "-n",
+            "--dry-run",
+            "-u",
+            "--upstream"
+        ]
+    );
+
+    let hints: Vec<String> = lua
+        .load(
+            r#"
+            local hint
+            for _, call in ipairs(_cmd_remote__add().calls) do
+                if call.method == "_addexarg" then
+                    hint = hint or call.args[1].hint
+                end
+            end
+            local given = "nested remote -u origin add "
+            parse(given, { { _cmd_remote(), "-u" } })
+            local missing = "nested remote add "
+            return {
+                hint(1, "", 5, line_state(given), {}),
+                hint(1, "", 3, line_state(missing), {}),
+            }
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(
+        hints,
+        vec![
+            "Argument expected: <name> | Add a remote | Missing: <url>",
+            "Argument expected: <name> | Add a remote | Missing: --upstream <url>",
+        ]
+    );
+}
+
+#[test]
+fn test_loop_until_takes_var_max_values() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("basic.kdl");
+    let module: mlua::Table = lua.load(generate(&spec, &["--module"])).eval().unwrap();
+    let loop_until: mlua::Function = module.get("loop_until").unwrap();
+    // The position starts at word 2, returning whether each word advances past it
+    let advance = |var_min: i64, var_max: i64, words: &[&str]| -> Vec<i64> {
+        lua.load(
+            r#"
+            local loop_until, var_min, var_max, words = ...
+            local line_state = { getword = function(_, i) return words[i] end }
+            local user_data = { var_min = var_min, var_max = var_max }
+            local advance = {}
+            for i = 2, #words + 1 do
+                table.insert(advance, loop_until(i, line_state, user_data))
+            end
+            return advance
+            "#,
+        )
+        .call((loop_until.clone(), var_min, var_max, words.to_vec()))
+        .unwrap()
+    };
+    assert_eq!(advance(2, 2, &["--pair", "a", "b"]), vec![0, 0, 1]);
+    assert_eq!(advance(0, 3, &["exec", "a", "b", "c"]), vec![0, 0, 0, 1]);
+    assert_eq!(advance(1, -1, &["rm", "a", "--"]), vec![0, 0, 1]);
+    // Not before var_min values were taken
+    assert_eq!(advance(2, -1, &["rm", "--", "a"]), vec![0, 0, 0]);
+}
+
+#[test]
+fn test_invalid_args_are_classified() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("validate.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    lua.load(
+        r#"
+        init_argmatchers()
+        -- Returns the class of the word given to the classifier of the matcher, if any
+        function classify(matcher, arg_index, word, word_count)
+            local classifier
+            for _, call in ipairs(matcher.calls) do
+                if call.method == "setclassifier" then
+                    classifier = call.args[1]
+                end
+            end
+            local class
+            local classifications = {
+                classifyword = function(_, _, c) class = c end,
+            }
+            local line_state = { getwordcount = function() return word_count or 9 end }
+            classifier(arg_index, word, 2, line_state, classifications)
+            return class
+        end
+        for _, matcher in ipairs(clink.argmatchers) do
+            if matcher.names[1] == "validate" then
+                root = matcher
+            end
+        end
+        "#,
+    )
+    .exec()
+    .unwrap();
+    let classify = |matcher: &str, arg_index: i64, word: &str, word_count: Option<i64>| {
+        lua.load(format!("return classify({}, ...)", matcher))
+            .call::<_, Option<String>>((arg_index, word, word_count))
+            .unwrap()
+    };
+
+    // A value out of the choices
+    assert_eq!(classify("root", 1, "dev", None), None);
+    assert_eq!(classify("root", 1, "staging", None), Some("n".into()));
+    assert_eq!(classify("_flag_level()", 1, "mid", None), Some("n".into()));
+    // Unless it's being typed
+    assert_eq!(classify("root", 1, "pr", Some(2)), None);
+    assert_eq!(classify("root", 1, "st", Some(2)), Some("n".into()));
+    // A subcmd in place of the required arg
+    assert_eq!(classify("root", 1, "d", None), Some("n".into()));
+    // Too many values
+    assert_eq!(classify("_cmd_deploy()", 1, "a.txt", None), None);
+    assert_eq!(
+        classify("_cmd_deploy()", 2, "c.txt", None),
+        Some("n".into())
+    );
+    // The flags are left to clink
+    assert_eq!(classify("root", 0, "--nope", None), None);
+}
+
+#[test]
+fn test_cmd_hints_list_the_missing_required_flags() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("validate.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let hints: Vec<String> = lua
+        .load(
+            r#"
+            init_argmatchers()
+            local hint
+            for _, call in ipairs(_cmd_deploy().calls) do
+                if call.method == "_addexarg" then
+                    hint = call.args[1].hint
+                end
+            end
+            return {
+                hint(1, "", 3, nil, {}),
+                hint(1, "", 5, nil, { used_flags = { ["-t"] = true } }),
+            }
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(
+        hints,
+        vec![
+            "Argument expected: <files>... [multiple args (0..2)] | Deploy to the env | Missing: --token",
+            "Argument expected: <files>... [multiple args (0..2)] | Deploy to the env",
+        ]
+    );
+}
+
+#[test]
+fn test_defaults_are_suggested_first() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("basic.kdl");
+    lua.load(generate(&spec, &["--suggest-defaults"]))
+        .exec()
+        .unwrap();
+    let firsts: Vec<String> = lua
+        .load(
+            r#"
+            init_argmatchers()
+            local firsts = {}
+            for _, matcher in ipairs({ _flag_color(), clink.argmatchers[1] }) do
+                for _, call in ipairs(matcher.calls) do
+                    local arg = call.args[1]
+                    if call.method == "_addexarg" and arg.nosort then
+                        table.insert(firsts, arg[1][1] .. " (" .. arg[1][2] .. ")")
+                    end
+                end
+            end
+            return firsts
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(firsts, vec!["auto (default)"]);
+
+    // Suggested along with the files, which are still sorted
+    let output: Vec<String> = lua
+        .load(
+            r#"
+            local output
+            for _, call in ipairs(clink.argmatchers[1].calls) do
+                if call.method == "_addexarg" then
+                    output = call.args[1]
+                end
+            end
+            assert(not output.nosort, "the files are unsorted")
+            return {
+                output[1][1] .. " (" .. output[1][2] .. ")",
+                tostring(output[2] == clink.filematches),
+            }
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(output, vec!["out.txt (default)", "true"]);
+}
+
+#[test]
+fn test_matches_keep_the_spec_order() {
+    let spec = common::tests_dir().join("fixtures").join("nested.kdl");
+    let unsorted = |args: &[&str]| -> Vec<String> {
+        let lua = clink_lua();
+        lua.load(generate(&spec, args)).exec().unwrap();
+        lua.load(
+            r#"
+            init_argmatchers()
+            local unsorted = {}
+            for name, matcher in pairs({ remote = _cmd_remote(), exec = _cmd_exec() }) do
+                for _, call in ipairs(matcher.calls) do
+                    if call.method:find("^_addex") and call.args[1].nosort then
+                        table.insert(unsorted, name .. call.method)
+                    end
+                end
+            end
+            table.sort(unsorted)
+            return unsorted
+            "#,
+        )
+        .eval()
+        .unwrap()
+    };
+    assert_eq!(unsorted(&[]), vec!["remote_addexarg", "remote_addexflags"]);
+    assert_eq!(
+        unsorted(&["--preserve-order"]),
+        vec![
+            "exec_addexarg",
+            "exec_addexarg",
+            "exec_addexflags",
+            "remote_addexarg",
+            "remote_addexflags"
+        ]
+    );
+}
+
+#[test]
+fn test_dynamic_matches_are_typed() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("complete.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let matches: Vec<String> = lua
+        .load(
+            r#"
+            local output = { "src/:Sources", "build.rs:Build script", "test:Run the tests", "dev" }
+            io.popen = function()
+                return {
+                    lines = function() return ipairs_values(output) end,
+                    close = function() end,
+                }
+            end
+            function ipairs_values(list)
+                local i = 0
+                return function()
+                    i = i + 1
+                    return list[i]
+                end
+            end
+            os.isdir = function() return false end
+            os.isfile = function(path) return path == "build.rs" end
+
+            local matches = {}
+            local match_builder = {
+                addmatch = function(_, match)
+                    local fields = { match.match, tostring(match.display), match.type, tostring(match.description) }
+                    table.insert(matches, table.concat(fields, " "))
+                end,
+            }
+            local state, word_index = line_state("complete run ")
+            local returned = _complete_arg_task("", word_index, state, match_builder, {})
+            assert(#returned == 0)
+            _complete_arg_profile("", word_index, state, match_builder, {})
+            return matches
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(
+        matches,
+        vec![
+            "src/ src/ dir Sources",
+            "build.rs build.rs file Build script",
+            "test test word Run the tests",
+            "dev dev word nil",
+            "src/:Sources src/:Sources word nil",
+            "build.rs:Build script build.rs:Build script word nil",
+            "test:Run the tests test:Run the tests word nil",
+            "dev dev word nil",
+        ]
+    );
+}
+
+#[test]
+fn test_async_completions_load_in_the_background() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("complete.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let steps: Vec<String> = lua
+        .load(
+            r#"
+            init_argmatchers()
+            -- Records the calls to clink, which runs the coroutines one step per idle tick and
+            -- resumes the one waiting on popenyield once the command is done
+            local steps, coroutines = {}, {}
+            clink.addcoroutine = function(co)
+                table.insert(steps, "addcoroutine")
+                table.insert(coroutines, co)
+            end
+            clink.reclassifyline = function() table.insert(steps, "reclassifyline") end
+            io.popen = function() error("run in the foreground") end
+            io.popenyield = function()
+                table.insert(steps, "popenyield")
+                coroutine.yield()
+                local lines = { "test:Run the tests" }
+                return {
+                    lines = function()
+                        table.insert(steps, "lines")
+                        return ipairs_values(lines)
+                    end,
+                    close = function() table.insert(steps, "close") end,
+                }
+            end
+            function ipairs_values(list)
+                local i = 0
+                return function()
+                    i = i + 1
+                    return list[i]
+                end
+            end
+            os.isdir = function() return false end
+            os.isfile = function() return false end
+
+            local function tick()
+                table.insert(steps, "tick")
+                local pending = {}
+                for _, co in ipairs(coroutines) do
+                    assert(coroutine.resume(co))
+                    if coroutine.status(co) ~= "dead" then
+                        table.insert(pending, co)
+                    end
+                end
+                coroutines = pending
+            end
+
+            local hint
+            for _, call in ipairs(_cmd_run().calls) do
+                hint = hint or call.args[1].hint
+            end
+            local function complete(line)
+                local matches = {}
+                local match_builder = {
+                    addmatch = function(_, match) table.insert(matches, match.match) end,
+                }
+                local state, word_index = line_state(line)
+                _complete_arg_task("", word_index, state, match_builder, {})
+                table.insert(steps, line .. "|" .. table.concat(matches, " ") .. "|"
+                    .. hint(1, "", word_index, state, {}))
+            end
+
+            complete("complete run ")
+            -- Completing the same line again doesn't run it again
+            complete("complete run ")
+            tick()
+            complete("complete run ")
+            tick()
+            complete("complete run ")
+            complete("complete run build ")
+            -- Another run was requested before the previous one started
+            complete("complete run build x ")
+            tick()
+            tick()
+            complete("complete run build x ")
+            -- Cached once loaded, although it's no longer the latest request
+            complete("complete run ")
+            return steps
+            "#,
+        )
+        .eval()
+        .unwrap();
+    let loaded = "|test|Argument expected: <task> | Run a task.";
+    assert_eq!(
+        steps,
+        vec![
+            "addcoroutine".to_string(),
+            "complete run ||Loading...".into(),
+            "complete run ||Loading...".into(),
+            // Shows the loading hint, then waits for the command
+            "tick".into(),
+            "reclassifyline".into(),
+            "popenyield".into(),
+            "complete run ||Loading...".into(),
+            // Reads its output, then shows it
+            "tick".into(),
+            "lines".into(),
+            "close".into(),
+            "reclassifyline".into(),
+            format!("complete run {}", loaded),
+            "addcoroutine".into(),
+            "complete run build ||Loading...".into(),
+            "addcoroutine".into(),
+            "complete run build x ||Loading...".into(),
+            // The first one is cancelled
+            "tick".into(),
+            "reclassifyline".into(),
+            "popenyield".into(),
+            "tick".into(),
+            "lines".into(),
+            "close".into(),
+            "reclassifyline".into(),
+            format!("complete run build x {}", loaded),
+            format!("complete run {}", loaded),
+        ]
+    );
+}
+
+#[test]
+fn test_dynamic_completions_are_cached() {
+    let lua = clink_lua();
+    let spec = common::tests_dir().join("fixtures").join("complete.kdl");
+    lua.load(generate(&spec, &[])).exec().unwrap();
+    let runs: Vec<i64> = lua
+        .load(
+            r#"
+            local runs = 0
+            io.popen = function()
+                runs = runs + 1
+                return { lines = function() return function() end end, close = function() end }
+            end
+            local now = 0
+            os.time = function() return now end
+            local cwd, env = "C:\\work", {}
+            os.getcwd = function() return cwd end
+            os.getenv = function(name) return env[name] end
+            local match_builder = { addmatch = function() end }
+            local function complete(completer, line)
+                local state, word_index = line_state(line)
+                completer("", word_index, state, match_builder, {})
+                return runs
+            end
+
+            local steps = {}
+            table.insert(steps, complete(_complete_arg_profile, "complete --profile "))
+            now = 29
+            table.insert(steps, complete(_complete_arg_profile, "complete --profile "))
+            now = 30
+            table.insert(steps, complete(_complete_arg_profile, "complete --profile "))
+            -- Keyed by the line the run is rendered from
+            table.insert(steps, complete(_complete_arg_task, "complete run "))
+            table.insert(steps, complete(_complete_arg_task, "complete run "))
+            table.insert(steps, complete(_complete_arg_task, "complete run t"))
+            now = 1000
+            table.insert(steps, complete(_complete_arg_task, "complete run t"))
+            -- By the cwd
+            cwd = "C:\\other"
+            table.insert(steps, complete(_complete_arg_task, "complete run t"))
+            cwd = "C:\\work"
+            table.insert(steps, complete(_complete_arg_task, "complete run t"))
+            -- And by the cache env vars
+            table.insert(steps, complete(_complete_arg_profile, "complete --profile "))
+            table.insert(steps, complete(_complete_arg_profile, "complete --profile "))
+            env.COMPLETE_PROFILE = "staging"
+            table.insert(steps, complete(_complete_arg_profile, "complete --profile "))
+            return steps
+            "#,
+        )
+        .eval()
+        .unwrap();
+    assert_eq!(runs, vec![1, 1, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7]);
+}
@@ -0,0 +1,257 @@
+//! Helpers shared by the integration tests.
+
+// Each test crate only uses some of the helpers
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Generated with the exe and the shell replaced by this, as they're absolute paths.
+const EXE_PLACEHOLDER: &str = "{{cuc}}";
+
+pub fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// The specs in `tests/fixtures`, sorted by name.
+pub fn fixture_specs() -> Vec<PathBuf> {
+    let mut specs: Vec<PathBuf> = fs::read_dir(tests_dir().join("fixtures"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "kdl"))
+        .collect();
+    specs.sort();
+    assert!(!specs.is_empty(), "no fixture specs found");
+    specs
+}
+
+pub fn generate(spec: &Path, args: &[&str]) -> String {
+    let bin_exe = Path::new(env!("CARGO_BIN_EXE_cuc"));
+    let exe = bin_exe.canonicalize().unwrap();
+    let output = Command::new(&exe)
+        .arg("generate")
+        .arg(spec)
+        .args(["--complete", "--shell"])
+        .arg(&exe)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "failed to generate {}: {}",
+        spec.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut generated = String::from_utf8(output.stdout).unwrap();
+    // The canonical path (e.g. `\\?\C:\...` on windows) contains the plain one, so it goes first
+    for path in [exe.as_path(), bin_exe] {
+        let path = path.display().to_string();
+        // Escaped by the string literals of the fig spec
+        let escaped = path.replace('\\', "\\\\");
+        generated = generated
+            .replace(&escaped, EXE_PLACEHOLDER)
+            .replace(&path, EXE_PLACEHOLDER);
+    }
+    generated
+}
+
+/// Runs cuc with the args, returning whether it succeeded along with its stdout and stderr.
+pub fn run_cuc(args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_cuc"))
+        .args(args)
+        .env_remove("RUST_BACKTRACE")
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// An empty directory for the test, in the temp dir.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cuc-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The vendored `arghelper.lua`, loaded by the stub as the scripts require it.
+const ARGHELPER: &str = include_str!("../../../../lib/cuc-lib/assets/arghelper.lua");
+
+/// Stubs of the clink api used by the generated scripts, recording the argmatchers and the
+/// methods called on them in `clink.argmatchers`. The methods added by arghelper are recorded
+/// too, before arghelper turns them into the calls of the clink api.
+const CLINK_STUB: &str = r#"
+local methods = {}
+function methods:setdelayinit(init)
+    self.delayinit = init
+    return self
+end
+
+local function record(self, method, ...)
+    table.insert(self.calls, { method = method, args = { ... } })
+end
+
+-- Left to arghelper, as clink before v1.3.9 doesn't have them
+local extended = { _addexflags = true, _addexarg = true }
+
+local argmatcher = {}
+argmatcher.__index = function(_, method)
+    if methods[method] then
+        return methods[method]
+    elseif extended[method] then
+        return nil
+    end
+    return function(self, ...)
+        record(self, method, ...)
+        return self
+    end
+end
+-- A flag linked to the argmatcher of its arg, e.g. `"--out" .. matcher`
+local link = {
+    __tostring = function(self)
+        return self._key
+    end,
+}
+argmatcher.__concat = function(word, matcher)
+    matcher.linked_to = word
+    return setmetatable({ _key = word, _matcher = matcher }, link)
+end
+
+clink = {
+    argmatchers = {},
+    filematches = function() end,
+    dirmatches = function() end,
+    arg = { register_parser = function() end },
+}
+function clink.argmatcher(...)
+    local matcher = setmetatable({ names = { ... }, calls = {} }, argmatcher)
+    table.insert(clink.argmatchers, matcher)
+    return matcher
+end
+-- Clink extends os, e.g. with getcwd
+function os.getcwd()
+    return "C:\\work"
+end
+
+-- A line state of the line, along with the index of the word being completed
+function line_state(line)
+    local words = {}
+    for word in line:gmatch("%S+") do
+        table.insert(words, word)
+    end
+    if line:match("%s$") then
+        table.insert(words, "")
+    end
+    local state = {
+        getline = function() return line end,
+        getword = function(_, i) return words[i] end,
+        getwordcount = function() return #words end,
+    }
+    return state, #words
+end
+
+-- Runs the delayed inits of all the argmatchers, as clink would on using each of them
+function init_argmatchers()
+    local pending = true
+    while pending do
+        pending = false
+        for _, matcher in ipairs(clink.argmatchers) do
+            local init = rawget(matcher, "delayinit")
+            if init then
+                matcher.delayinit = nil
+                init(matcher)
+                pending = true
+            end
+        end
+    end
+end
+
+-- The flags given to clink by arghelper for the argmatcher
+function clink_flags(matcher)
+    for _, call in ipairs(matcher.calls) do
+        if call.method == "addflags" then
+            return call.args[1]
+        end
+    end
+end
+
+-- Completes the flags of the argmatcher as clink does, once the words were parsed by it:
+-- the functions of the flags add the filters of the matches listed by their words
+function complete_flags(matcher, line, words)
+    local flags = clink_flags(matcher)
+    local state = line_state(line)
+    local user_data = {}
+    for _, word in ipairs(words) do
+        flags.onarg(0, word, 0, state, user_data)
+    end
+    local matches = {}
+    local filters = {}
+    local onfiltermatches = clink.onfiltermatches
+    clink.onfiltermatches = function(filter) table.insert(filters, filter) end
+    for _, entry in ipairs(flags) do
+        if type(entry) == "function" then
+            entry("", 0, state, nil, user_data)
+        elseif type(entry) ~= "table" or getmetatable(entry) == link then
+            table.insert(matches, { match = tostring(entry) })
+        end
+    end
+    clink.onfiltermatches = onfiltermatches
+    for _, filter in ipairs(filters) do
+        matches = filter(matches)
+    end
+    local names = {}
+    for _, match in ipairs(matches) do
+        table.insert(names, match.match)
+    end
+    return names
+end
+
+-- Records the methods of arghelper along with the ones of the stub
+require("arghelper")
+local arghelper_index = argmatcher.__index
+argmatcher.__index = function(self, method)
+    local value = arghelper_index(self, method)
+    if not extended[method] then
+        return value
+    end
+    return function(self, ...)
+        record(self, method, ...)
+        return value(self, ...)
+    end
+end
+-- Made by arghelper to check the methods of clink, it isn't one of the scripts
+clink.argmatchers = {}
+"#;
+
+/// A lua state with the clink api stubbed, to load the generated scripts.
+pub fn clink_lua() -> mlua::Lua {
+    // Clink provides the debug library, which isn't loaded in the safe mode
+    // SAFETY: the scripts don't use the debug library beyond getting their own path
+    let lua = unsafe { mlua::Lua::unsafe_new() };
+    {
+        let arghelper = lua.load(ARGHELPER).set_name("arghelper");
+        let preload: mlua::Table = lua.load("package.preload").eval().unwrap();
+        preload
+            .set("arghelper", arghelper.into_function().unwrap())
+            .unwrap();
+    }
+    lua.load(CLINK_STUB).set_name("clink_stub").exec().unwrap();
+    lua
+}
+
+/// The names of the global variables.
+pub fn lua_globals(lua: &mlua::Lua) -> Vec<String> {
+    let mut names: Vec<String> = lua
+        .globals()
+        .pairs::<String, mlua::Value>()
+        .map(|pair| pair.unwrap().0)
+        .collect();
+    names.sort();
+    names
+}
@@ -0,0 +1,57 @@
+//! Golden-file tests of `cuc generate` over the specs in `tests/fixtures`.
+//!
+//! The expected outputs live in `tests/golden`. Run with `UPDATE_GOLDEN=1` to regenerate them
+//! after an intended change of the output, and review the diff.
+
+mod common;
+
+use std::fs;
+
+use common::{fixture_specs, generate, tests_dir};
+
+/// The file extension of the golden file and the extra args of `cuc generate`.
+const VARIANTS: &[(&str, &[&str])] = &[
+    ("lua", &["--target", "clink"]),
+    ("min.lua", &["--target", "clink", "--minify"]),
+    ("module.lua", &["--target", "clink", "--module"]),
+    ("ts", &["--target", "fig"]),
+];
+
+#[test]
+fn test_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let specs = fixture_specs();
+    let mut mismatches = vec![];
+    for spec in specs.iter() {
+        let stem = spec.file_stem().unwrap().to_str().unwrap();
+        for (ext, args) in VARIANTS {
+            let generated = generate(spec, args);
+            // Each run must be byte-identical, regardless of hashing or allocation
+            for _ in 0..3 {
+                assert_eq!(
+                    generate(spec, args),
+                    generated,
+                    "output of {} ({}) differs between runs",
+                    spec.display(),
+                    ext
+                );
+            }
+
+            let golden = tests_dir().join("golden").join(format!("{}.{}", stem, ext));
+            if update {
+                fs::create_dir_all(golden.parent().unwrap()).unwrap();
+                fs::write(&golden, &generated).unwrap();
+                continue;
+            }
+            match fs::read_to_string(&golden) {
+                Ok(expected) if expected == generated => {}
+                _ => mismatches.push(golden.display().to_string()),
+            }
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "generated output doesn't match the golden files (rerun with UPDATE_GOLDEN=1 to update):\n{}",
+        mismatches.join("\n")
+    );
+}
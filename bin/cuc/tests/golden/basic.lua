@@ -0,0 +1,174 @@
+require("arghelper")
+-- The argmatchers of the functions, built once on first call
+local matchers = {}
+
+local function loop_until(word_index, line_state, user_data)
+	if not user_data.first_index then
+		user_data.first_index = word_index
+	end
+	local taken = word_index - user_data.first_index
+	local prev_word = line_state:getword(word_index - 1)
+	-- Advance to the next arg position once var_max words were taken, or on '--'
+	-- once var_min were. var_max is -1 to loop until '--'.
+	if (user_data.var_max >= 0 and taken >= user_data.var_max) or (prev_word == "--" and taken >= user_data.var_min) then
+		return 1
+	end
+	return 0
+end
+
+local function delay_init(matcher, init)
+	if matcher.setdelayinit then
+		matcher:setdelayinit(init)
+	else
+		init(matcher)
+	end
+	return matcher
+end
+
+local function classify_args(matcher, args)
+	if not matcher.setclassifier then
+		return
+	end
+	local cmds = {}
+	for _, cmd in ipairs(args.cmds or {}) do
+		cmds[cmd] = true
+	end
+	matcher:setclassifier(function(arg_index, word, word_index, line_state, classifications)
+		local invalid
+		if arg_index == 0 then
+			-- The flags are classified by clink
+			return
+		elseif arg_index == 1 and cmds[word] then
+			invalid = args.required
+		elseif args.max and arg_index > args.max then
+			invalid = true
+		elseif args[arg_index] then
+			local typing = word_index == line_state:getwordcount()
+			invalid = true
+			for _, choice in ipairs(args[arg_index]) do
+				if choice == word or (typing and choice:sub(1, #word) == word) then
+					return
+				end
+			end
+		end
+		if invalid then
+			classifications:classifyword(word_index, "n")
+			return true
+		end
+	end)
+end
+
+function _flag_color()
+	matchers._flag_color = matchers._flag_color or delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({ "always", "auto", "never", hint = [=[Argument expected: <when> [default: auto]]=] })
+		classify_args(matcher, { { "always", "auto", "never" } })
+	end)
+	return matchers._flag_color
+end
+
+function _flag_include()
+	matchers._flag_include = matchers._flag_include or delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({ hint = [[Argument expected: <path>]] })
+	end)
+	return matchers._flag_include
+end
+
+function _flag_pair()
+	matchers._flag_pair = matchers._flag_pair or delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({
+			hint = [=[Argument expected: <kv> [multiple args (2..2)]]=],
+			onadvance = function(_, _, wi, ls, ud)
+				ud.var_min = 2
+				ud.var_max = 2
+				return loop_until(wi, ls, ud)
+			end
+		})
+		classify_args(matcher, { false, max = 1 })
+	end)
+	return matchers._flag_pair
+end
+
+local function flag_used(used, names)
+	used = used or {}
+	for name in names:gmatch("%S+") do
+		if used[name] then
+			return true
+		end
+	end
+	return false
+end
+
+local used_global_flags = {}
+
+local function line_flags(line_state)
+	local line = line_state:getline()
+	if used_global_flags.line ~= line then
+		used_global_flags.line = line
+		used_global_flags.names = {}
+	end
+	return used_global_flags.names
+end
+
+local function hide_used(flags)
+	local once = {}
+	local global = {}
+	local function scan(entries)
+		for _, entry in ipairs(entries) do
+			if type(entry) == "table" then
+				if entry.global then
+					for name in entry.global:gmatch("%S+") do
+						global[name] = true
+					end
+				end
+				if entry.once then
+					for name in entry.once:gmatch("%S+") do
+						once[name] = entry.once
+					end
+				else
+					scan(entry)
+				end
+			end
+		end
+	end
+	scan(flags)
+	-- The arg index is 0 for the flags, recorded for flag_used
+	flags.onarg = function(arg_index, word, _, line_state, user_data)
+		if arg_index == 0 then
+			local name = (word:gsub("[:=].*$", ""))
+			user_data.used_flags = user_data.used_flags or {}
+			user_data.used_flags[name] = true
+			if global[name] then
+				line_flags(line_state)[name] = true
+			end
+		end
+	end
+	table.insert(flags, function(_, _, line_state, _, user_data)
+		clink.onfiltermatches(function(matches)
+			local unused = {}
+			for _, match in ipairs(matches) do
+				local names = once[match.match]
+				local used = global[match.match] and line_flags(line_state) or user_data.used_flags
+				if not (names and flag_used(used, names)) then
+					table.insert(unused, match)
+				end
+			end
+			return unused
+		end)
+		return {}
+	end)
+	return flags
+end
+
+delay_init(clink.argmatcher("basic"), function(matcher)
+	matcher:_addexflags(hide_used({
+		{ "-v", [[Print more output]], once = "-v --verbose" },
+		{ "--verbose", [[Print more output]], once = "-v --verbose" },
+		{ "--color" .. _flag_color(), " <when>", [[When to use colors]], once = "--color" },
+		{ "-I" .. _flag_include(), " <path>", [[Add a search path]] },
+		{ "--include" .. _flag_include(), " <path>", [[Add a search path]] },
+		{ "--pair" .. _flag_pair(), " <kv>", [[Set a key to a value]], once = "--pair" }
+	}))
+		:_addexarg({ hint = [[Argument expected: <input>]] })
+		:_addexarg({ clink.filematches, hint = [=[Argument expected: [output] [default: out.txt]]=] })
+		:nofiles()
+end)
@@ -0,0 +1,47 @@
+const completionSpec: Fig.Spec = {
+  name: "basic",
+  options: [
+    {
+      name: ["-v", "--verbose"],
+      description: "Print more output",
+    },
+    {
+      name: "--color",
+      description: "When to use colors",
+      args: {
+        name: "when",
+        default: "auto",
+        suggestions: ["always", "auto", "never"],
+      },
+    },
+    {
+      name: ["-I", "--include"],
+      description: "Add a search path",
+      isRepeatable: true,
+      args: {
+        name: "path",
+      },
+    },
+    {
+      name: "--pair",
+      description: "Set a key to a value",
+      args: {
+        name: "kv",
+        isVariadic: true,
+      },
+    },
+  ],
+  args: [
+    {
+      name: "input",
+    },
+    {
+      name: "output",
+      isOptional: true,
+      default: "out.txt",
+      template: "filepaths",
+    },
+  ],
+};
+
+export default completionSpec;
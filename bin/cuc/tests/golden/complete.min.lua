@@ -0,0 +1,2 @@
+require("arghelper") local matchers={} local function loop_until(word_index,line_state,user_data) if not user_data.first_index then user_data.first_index=word_index end local taken=word_index - user_data.first_index local prev_word=line_state:getword(word_index - 1) if (user_data.var_max >= 0 and taken >= user_data.var_max) or (prev_word == "--" and taken >= user_data.var_min) then return 1 end return 0 end local function delay_init(matcher,init) if matcher.setdelayinit then matcher:setdelayinit(init) else init(matcher) end return matcher end local function quote_arg(arg) arg=arg:gsub("(\\*)\"","%1%1\\\""):gsub("(\\+)$","%1%1") return "\"" .. arg .. "\"" end local function read_lines(pipe) assert(pipe,"[ERROR]: failed to run complete command") local lines={} for line in pipe:lines() do table.insert(lines,line) end pipe:close() return lines end local completion_cache={} local function cached_lines(name,key,ttl) local cached=completion_cache[name] and completion_cache[name][key] if cached and (not ttl or os.time() - cached.time < ttl) then return cached.lines end end local function cache_lines(name,key,lines) completion_cache[name]=completion_cache[name] or {} completion_cache[name][key]={lines=lines,time=os.time()} end function _complete_arg_profile(word,word_index,line_state,match_builder,user_data) local b64_encoded_script=[[cHJpbnRmICdkZXZcbnByb2Rcbic=]] local exec=[[{{cuc}}]] local shell=[[{{cuc}}]] local line_hex=line_state:getline():gsub(".",function(c) return string.format("%02x",c:byte()) end) local args=" complete --current " .. word_index - 1 .. " --line-hex " .. line_hex .. " --shell " .. quote_arg(shell) .. " --cache-ttl 3600 --cache-env COMPLETE_PROFILE -- " .. b64_encoded_script local command="\"" .. quote_arg(exec) .. args .. "\"" local key=table.concat({args,os.getcwd(),os.getenv("COMPLETE_PROFILE") or ""},"\000") local lines=cached_lines("profile",key,30) if not lines then lines=read_lines(io.popen(command)) cache_lines("profile",key,lines) end for _,line in ipairs(lines) do match_builder:addmatch({match=line,display=line,type="word"}) end return {} end function _flag_profile() matchers._flag_profile=matchers._flag_profile or delay_init(clink.argmatcher(),function(matcher) matcher:_addexarg({_complete_arg_profile,hint=[[Argument expected: <profile>]],nosort=true}) end) return matchers._flag_profile end local function classify_args(matcher,args) if not matcher.setclassifier then return end local cmds={} for _,cmd in ipairs(args.cmds or {}) do cmds[cmd]=true end matcher:setclassifier(function(arg_index,word,word_index,line_state,classifications) local invalid if arg_index == 0 then return elseif arg_index == 1 and cmds[word] then invalid=args.required elseif args.max and arg_index > args.max then invalid=true elseif args[arg_index] then local typing=word_index == line_state:getwordcount() invalid=true for _,choice in ipairs(args[arg_index]) do if choice == word or (typing and choice:sub(1,#word) == word) then return end end end if invalid then classifications:classifyword(word_index,"n") return true end end) end function _flag_shell() matchers._flag_shell=matchers._flag_shell or delay_init(clink.argmatcher(),function(matcher) matcher:_addexarg({"bash","cmd \"/c\"","C:\\tools\\sh.exe",hint=[[Argument expected: <shell>]]}) classify_args(matcher,{{"bash","cmd \"/c\"","C:\\tools\\sh.exe"}}) end) return matchers._flag_shell end local function flag_used(used,names) used=used or {} for name in names:gmatch("%S+") do if used[name] then return true end end return false end local used_global_flags={} local function line_flags(line_state) local line=line_state:getline() if used_global_flags.line ~= line then used_global_flags.line=line used_global_flags.names={} end return used_global_flags.names end local function hide_used(flags) local once={} local global={} local function scan(entries) for _,entry in ipairs(entries) do if type(entry) == "table" then if entry.global then for name in entry.global:gmatch("%S+") do global[name]=true end end if entry.once then for name in entry.once:gmatch("%S+") do once[name]=entry.once end else scan(entry) end end end end scan(flags) flags.onarg=function(arg_index,word,_,line_state,user_data) if arg_index == 0 then local name=(word:gsub("[:=].*$","")) user_data.used_flags=user_data.used_flags or {} user_data.used_flags[name]=true if global[name] then line_flags(line_state)[name]=true end end end table.insert(flags,function(_,_,line_state,_,user_data) clink.onfiltermatches(function(matches) local unused={} for _,match in ipairs(matches) do local names=once[match.match] local used=global[match.match] and line_flags(line_state) or user_data.used_flags if not (names and flag_used(used,names)) then table.insert(unused,match) end end return unused end) return {} end) return flags end local function cmd_hints(cmd) return function(hint) return function(arg_index,_,_,line_state,user_data) local parts={} if hint then table.insert(parts,hint) end if cmd.help then table.insert(parts,cmd.help) end local missing={} for _,names in ipairs(cmd.flags or {}) do if not flag_used(user_data.used_flags,names) then table.insert(missing,names:match("%-%-%S+") or names:match("%S+")) end end for _,names in ipairs(cmd.global_flags or {}) do if not flag_used(line_flags(line_state),names) then table.insert(missing,names:match("%-%-%S+") or names:match("%S+")) end end for i,arg in ipairs(cmd.args or {}) do if i > arg_index and arg then table.insert(missing,arg) end end if #missing > 0 then table.insert(parts,"Missing: " .. table.concat(missing," ")) end if #parts > 0 then return table.concat(parts," | ") end end end end local async_completion={} local function complete_async(name,key,line,command,store) local request=async_completion if request.name == name and request.key == key and (not request.lines or request.line == line) then return request.lines end if not (io.popenyield and clink.addcoroutine) then local lines=read_lines(io.popen(command)) if store then store(name,key,lines) end return lines end request={name=name,key=key,line=line} async_completion=request clink.addcoroutine(coroutine.create(function() if async_completion ~= request then return end clink.reclassifyline() request.lines=read_lines(io.popenyield(command)) if store then store(name,key,request.lines) end if async_completion == request then clink.reclassifyline() end end)) end local function match_type(match) if match:find("[/\\]$") or os.isdir(match) then return "dir" elseif os.isfile(match) then return "file" end return "word" end function _complete_arg_task(word,word_index,line_state,match_builder,user_data) local b64_encoded_script=[[ZWNobyAie3t3b3Jkc1tQUkVWXX19IiBdXSBpdCdz]] local exec=[[{{cuc}}]] local shell=[[{{cuc}}]] local line_hex=line_state:getline():gsub(".",function(c) return string.format("%02x",c:byte()) end) local args=" complete --current " .. word_index - 1 .. " --line-hex " .. line_hex .. " --shell " .. quote_arg(shell) .. " -- " .. b64_encoded_script local command="\"" .. quote_arg(exec) .. args .. "\"" local key=table.concat({args,os.getcwd()},"\000") local lines=cached_lines("task",key,nil) or complete_async("task",key,line_state:getline(),command,cache_lines) if not lines then if match_builder.setvolatile then match_builder:setvolatile() end return {} end for _,line in ipairs(lines) do local match=line:match("^([^:]+):") or line local description=line:match("^[^:]+:(.*)$") match_builder:addmatch({match=match,display=match,description=description,type=match_type(match)}) end return {} end local function loading_hint(name,hint) return function(arg_index,word,word_index,line_state,user_data) local request=async_completion if request.name == name and not request.lines then return "Loading..." end if type(hint) == "function" then return hint(arg_index,word,word_index,line_state,user_data) end return hint end end function _cmd_run() matchers._cmd_run=matchers._cmd_run or delay_init(clink.argmatcher(),function(matcher) local hint=cmd_hints({help=[[Run a task.]]}) matcher:_addexarg({_complete_arg_task,hint=loading_hint("task",hint([[Argument expected: <task>]]))}):_addexarg({clink.filematches,hint=hint([=[Argument expected: [file]]=])}):_addexarg({clink.dirmatches,hint=hint([=[Argument expected: [dir]]=])}) end) return matchers._cmd_run end delay_init(clink.argmatcher("complete"),function(matcher) matcher:_addexflags(hide_used({{"--profile" .. _flag_profile()," <profile>",[=[Profile to use, e.g. "dev" or ]]prod]]]=],opteq=true,once="--profile"},{"--shell" .. _flag_shell()," <shell>",once="--shell"}})):_addexarg({{"run" .. _cmd_run(),[[Run a task.
+Tasks are read from the ]=] config]]}}):nofiles() end)
\ No newline at end of file
@@ -0,0 +1,104 @@
+const _line_hex = (tokens: string[]): string =>
+  Array.from(
+    new TextEncoder().encode(
+      tokens
+        .map((token) => token.replace(/(\\*)"/g, '$1$1\\"').replace(/(\\+)$/, "$1$1"))
+        .map((token) => `"${token}"`)
+        .join(" "),
+    ),
+    (byte) => byte.toString(16).padStart(2, "0"),
+  ).join("");
+
+const _complete_arg_profile: Fig.Generator = {
+  // printf 'dev\nprod\n'
+  script: (tokens) => [
+    "{{cuc}}",
+    "complete",
+    "--current",
+    String(tokens.length - 1),
+    "--line-hex",
+    _line_hex(tokens),
+    "--shell",
+    "{{cuc}}",
+    "--cache-ttl",
+    "3600",
+    "--cache-env",
+    "COMPLETE_PROFILE",
+    "--",
+    "cHJpbnRmICdkZXZcbnByb2Rcbic=",
+  ],
+  postProcess: (out) =>
+    out
+      .split("\n")
+      .filter((line) => line.length > 0)
+      .map((line) => ({ name: line })),
+};
+
+const _complete_arg_task: Fig.Generator = {
+  // echo "{{words[PREV]}}" ]] it's
+  script: (tokens) => [
+    "{{cuc}}",
+    "complete",
+    "--current",
+    String(tokens.length - 1),
+    "--line-hex",
+    _line_hex(tokens),
+    "--shell",
+    "{{cuc}}",
+    "--",
+    "ZWNobyAie3t3b3Jkc1tQUkVWXX19IiBdXSBpdCdz",
+  ],
+  postProcess: (out) =>
+    out
+      .split("\n")
+      .filter((line) => line.length > 0)
+      .map((line) => {
+        const [name, ...description] = line.split(":");
+        return { name, description: description.join(":") };
+      }),
+};
+
+const completionSpec: Fig.Spec = {
+  name: "complete",
+  options: [
+    {
+      name: "--profile",
+      description: "Profile to use, e.g. \"dev\" or ]]prod]]",
+      args: {
+        name: "profile",
+        generators: _complete_arg_profile,
+      },
+    },
+    {
+      name: "--shell",
+      args: {
+        name: "shell",
+        suggestions: ["bash", "cmd \"/c\"", "C:\\tools\\sh.exe"],
+      },
+    },
+  ],
+  subcommands: [
+    {
+      name: "run",
+      description: "Run a task.\nTasks are read from the ]=] config",
+      args: [
+        {
+          name: "task",
+          generators: _complete_arg_task,
+        },
+        {
+          name: "file",
+          isOptional: true,
+          template: "filepaths",
+        },
+        {
+          name: "dir",
+          isOptional: true,
+          template: "folders",
+        },
+      ],
+    },
+  ],
+};
+
+export default completionSpec;
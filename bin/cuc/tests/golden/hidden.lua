@@ -0,0 +1,194 @@
+require("arghelper")
+-- The argmatchers of the functions, built once on first call
+local matchers = {}
+
+local function loop_until(word_index, line_state, user_data)
+	if not user_data.first_index then
+		user_data.first_index = word_index
+	end
+	local taken = word_index - user_data.first_index
+	local prev_word = line_state:getword(word_index - 1)
+	-- Advance to the next arg position once var_max words were taken, or on '--'
+	-- once var_min were. var_max is -1 to loop until '--'.
+	if (user_data.var_max >= 0 and taken >= user_data.var_max) or (prev_word == "--" and taken >= user_data.var_min) then
+		return 1
+	end
+	return 0
+end
+
+local function delay_init(matcher, init)
+	if matcher.setdelayinit then
+		matcher:setdelayinit(init)
+	else
+		init(matcher)
+	end
+	return matcher
+end
+
+local function flag_used(used, names)
+	used = used or {}
+	for name in names:gmatch("%S+") do
+		if used[name] then
+			return true
+		end
+	end
+	return false
+end
+
+local used_global_flags = {}
+
+local function line_flags(line_state)
+	local line = line_state:getline()
+	if used_global_flags.line ~= line then
+		used_global_flags.line = line
+		used_global_flags.names = {}
+	end
+	return used_global_flags.names
+end
+
+local function hide_used(flags)
+	local once = {}
+	local global = {}
+	local function scan(entries)
+		for _, entry in ipairs(entries) do
+			if type(entry) == "table" then
+				if entry.global then
+					for name in entry.global:gmatch("%S+") do
+						global[name] = true
+					end
+				end
+				if entry.once then
+					for name in entry.once:gmatch("%S+") do
+						once[name] = entry.once
+					end
+				else
+					scan(entry)
+				end
+			end
+		end
+	end
+	scan(flags)
+	-- The arg index is 0 for the flags, recorded for flag_used
+	flags.onarg = function(arg_index, word, _, line_state, user_data)
+		if arg_index == 0 then
+			local name = (word:gsub("[:=].*$", ""))
+			user_data.used_flags = user_data.used_flags or {}
+			user_data.used_flags[name] = true
+			if global[name] then
+				line_flags(line_state)[name] = true
+			end
+		end
+	end
+	table.insert(flags, function(_, _, line_state, _, user_data)
+		clink.onfiltermatches(function(matches)
+			local unused = {}
+			for _, match in ipairs(matches) do
+				local names = once[match.match]
+				local used = global[match.match] and line_flags(line_state) or user_data.used_flags
+				if not (names and flag_used(used, names)) then
+					table.insert(unused, match)
+				end
+			end
+			return unused
+		end)
+		return {}
+	end)
+	return flags
+end
+
+local function cmd_hints(cmd)
+	return function(hint)
+		return function(arg_index, _, _, line_state, user_data)
+			local parts = {}
+			if hint then
+				table.insert(parts, hint)
+			end
+			if cmd.help then
+				table.insert(parts, cmd.help)
+			end
+			local missing = {}
+			for _, names in ipairs(cmd.flags or {}) do
+				if not flag_used(user_data.used_flags, names) then
+					table.insert(missing, names:match("%-%-%S+") or names:match("%S+"))
+				end
+			end
+			for _, names in ipairs(cmd.global_flags or {}) do
+				if not flag_used(line_flags(line_state), names) then
+					table.insert(missing, names:match("%-%-%S+") or names:match("%S+"))
+				end
+			end
+			-- The required args past the current one
+			for i, arg in ipairs(cmd.args or {}) do
+				if i > arg_index and arg then
+					table.insert(missing, arg)
+				end
+			end
+			if #missing > 0 then
+				table.insert(parts, "Missing: " .. table.concat(missing, " "))
+			end
+			if #parts > 0 then
+				return table.concat(parts, " | ")
+			end
+		end
+	end
+end
+
+local function classify_args(matcher, args)
+	if not matcher.setclassifier then
+		return
+	end
+	local cmds = {}
+	for _, cmd in ipairs(args.cmds or {}) do
+		cmds[cmd] = true
+	end
+	matcher:setclassifier(function(arg_index, word, word_index, line_state, classifications)
+		local invalid
+		if arg_index == 0 then
+			-- The flags are classified by clink
+			return
+		elseif arg_index == 1 and cmds[word] then
+			invalid = args.required
+		elseif args.max and arg_index > args.max then
+			invalid = true
+		elseif args[arg_index] then
+			local typing = word_index == line_state:getwordcount()
+			invalid = true
+			for _, choice in ipairs(args[arg_index]) do
+				if choice == word or (typing and choice:sub(1, #word) == word) then
+					return
+				end
+			end
+		end
+		if invalid then
+			classifications:classifyword(word_index, "n")
+			return true
+		end
+	end)
+end
+
+function _cmd_build()
+	matchers._cmd_build = matchers._cmd_build or delay_init(clink.argmatcher(), function(matcher)
+		local hint = cmd_hints({ help = [[Build the project]] })
+		matcher:_addexarg({ "debug", "release", hint = hint([[Argument expected: <target>]]) })
+			:_addexarg({})
+			:_addexarg({ "1", "2", "4", hint = hint([=[Argument expected: [jobs]]=]) })
+		classify_args(matcher, {
+			{ "debug", "release" },
+			false,
+			{ "1", "2", "4" }
+		})
+	end)
+	return matchers._cmd_build
+end
+
+delay_init(clink.argmatcher("hidden"), function(matcher)
+	matcher:_addexflags(hide_used({
+		{ "-v", [[Print more]], once = "-v --verbose" },
+		{ "--verbose", [[Print more]], once = "-v --verbose" }
+	}))
+		:_addexarg({
+			{ "build" .. _cmd_build(), [[Build the project]] },
+			--[[alias]] { "b" .. _cmd_build(), [[Build the project]] }
+		})
+		:nofiles()
+end)
@@ -0,0 +1,32 @@
+const completionSpec: Fig.Spec = {
+  name: "hidden",
+  options: [
+    {
+      name: ["-v", "--verbose"],
+      description: "Print more",
+    },
+  ],
+  subcommands: [
+    {
+      name: ["build", "b"],
+      description: "Build the project",
+      args: [
+        {
+          name: "target",
+          suggestions: ["debug", "release"],
+        },
+        {
+          name: "profile",
+          isOptional: true,
+        },
+        {
+          name: "jobs",
+          isOptional: true,
+          suggestions: ["1", "2", "4"],
+        },
+      ],
+    },
+  ],
+};
+
+export default completionSpec;
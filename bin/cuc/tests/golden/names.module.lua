@@ -0,0 +1,150 @@
+require("arghelper")
+local cuc_names = {}
+-- The argmatchers of the functions, built once on first call
+local matchers = {}
+
+function cuc_names.loop_until(word_index, line_state, user_data)
+	if not user_data.first_index then
+		user_data.first_index = word_index
+	end
+	local taken = word_index - user_data.first_index
+	local prev_word = line_state:getword(word_index - 1)
+	-- Advance to the next arg position once var_max words were taken, or on '--'
+	-- once var_min were. var_max is -1 to loop until '--'.
+	if (user_data.var_max >= 0 and taken >= user_data.var_max) or (prev_word == "--" and taken >= user_data.var_min) then
+		return 1
+	end
+	return 0
+end
+
+function cuc_names.delay_init(matcher, init)
+	if matcher.setdelayinit then
+		matcher:setdelayinit(init)
+	else
+		init(matcher)
+	end
+	return matcher
+end
+
+function cuc_names._flag_foo_bar__dry_run()
+	matchers._flag_foo_bar__dry_run = matchers._flag_foo_bar__dry_run or cuc_names.delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({ hint = [[Argument expected: <mode>]] })
+	end)
+	return matchers._flag_foo_bar__dry_run
+end
+
+function cuc_names.flag_used(used, names)
+	used = used or {}
+	for name in names:gmatch("%S+") do
+		if used[name] then
+			return true
+		end
+	end
+	return false
+end
+
+cuc_names.used_global_flags = {}
+
+function cuc_names.line_flags(line_state)
+	local line = line_state:getline()
+	if cuc_names.used_global_flags.line ~= line then
+		cuc_names.used_global_flags.line = line
+		cuc_names.used_global_flags.names = {}
+	end
+	return cuc_names.used_global_flags.names
+end
+
+function cuc_names.hide_used(flags)
+	local once = {}
+	local global = {}
+	local function scan(entries)
+		for _, entry in ipairs(entries) do
+			if type(entry) == "table" then
+				if entry.global then
+					for name in entry.global:gmatch("%S+") do
+						global[name] = true
+					end
+				end
+				if entry.once then
+					for name in entry.once:gmatch("%S+") do
+						once[name] = entry.once
+					end
+				else
+					scan(entry)
+				end
+			end
+		end
+	end
+	scan(flags)
+	-- The arg index is 0 for the flags, recorded for flag_used
+	flags.onarg = function(arg_index, word, _, line_state, user_data)
+		if arg_index == 0 then
+			local name = (word:gsub("[:=].*$", ""))
+			user_data.used_flags = user_data.used_flags or {}
+			user_data.used_flags[name] = true
+			if global[name] then
+				cuc_names.line_flags(line_state)[name] = true
+			end
+		end
+	end
+	table.insert(flags, function(_, _, line_state, _, user_data)
+		clink.onfiltermatches(function(matches)
+			local unused = {}
+			for _, match in ipairs(matches) do
+				local names = once[match.match]
+				local used = global[match.match] and cuc_names.line_flags(line_state) or user_data.used_flags
+				if not (names and cuc_names.flag_used(used, names)) then
+					table.insert(unused, match)
+				end
+			end
+			return unused
+		end)
+		return {}
+	end)
+	return flags
+end
+
+function cuc_names._cmd_foo_bar()
+	matchers._cmd_foo_bar = matchers._cmd_foo_bar or cuc_names.delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexflags(cuc_names.hide_used({ { "--dry-run" .. cuc_names._flag_foo_bar__dry_run(), " <mode>", once = "--dry-run" } }))
+	end)
+	return matchers._cmd_foo_bar
+end
+
+function cuc_names._flag_foo_bar__dry_run___daad5702()
+	matchers._flag_foo_bar__dry_run___daad5702 = matchers._flag_foo_bar__dry_run___daad5702 or cuc_names.delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({ hint = [[Argument expected: <mode>]] })
+	end)
+	return matchers._flag_foo_bar__dry_run___daad5702
+end
+
+function cuc_names._cmd_foo_bar___b59d71d8()
+	matchers._cmd_foo_bar___b59d71d8 = matchers._cmd_foo_bar___b59d71d8 or cuc_names.delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexflags(cuc_names.hide_used({ { "--dry_run" .. cuc_names._flag_foo_bar__dry_run___daad5702(), " <mode>", once = "--dry_run" } }))
+	end)
+	return matchers._cmd_foo_bar___b59d71d8
+end
+
+function cuc_names._cmd_foobar__caf____4d598f5b()
+	matchers._cmd_foobar__caf____4d598f5b = matchers._cmd_foobar__caf____4d598f5b or cuc_names.delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({ hint = [[Argument expected: <thé>]] })
+	end)
+	return matchers._cmd_foobar__caf____4d598f5b
+end
+
+function cuc_names._cmd_foobar()
+	matchers._cmd_foobar = matchers._cmd_foobar or cuc_names.delay_init(clink.argmatcher(), function(matcher)
+		matcher:_addexarg({ { "café" .. cuc_names._cmd_foobar__caf____4d598f5b() } })
+	end)
+	return matchers._cmd_foobar
+end
+
+cuc_names.delay_init(clink.argmatcher("names"), function(matcher)
+	matcher:_addexarg({
+		{ "foo-bar" .. cuc_names._cmd_foo_bar() },
+		{ "foo_bar" .. cuc_names._cmd_foo_bar___b59d71d8() },
+		{ "foobar" .. cuc_names._cmd_foobar() }
+	})
+		:nofiles()
+end)
+return cuc_names
@@ -0,0 +1,42 @@
+const completionSpec: Fig.Spec = {
+  name: "names",
+  subcommands: [
+    {
+      name: "foo-bar",
+      options: [
+        {
+          name: "--dry-run",
+          args: {
+            name: "mode",
+          },
+        },
+      ],
+    },
+    {
+      name: "foo_bar",
+      options: [
+        {
+          name: "--dry_run",
+          args: {
+            name: "mode",
+          },
+        },
+      ],
+    },
+    {
+      name: "foobar",
+      subcommands: [
+        {
+          name: "café",
+          args: [
+            {
+              name: "thé",
+            },
+          ],
+        },
+      ],
+    },
+  ],
+};
+
+export default completionSpec;
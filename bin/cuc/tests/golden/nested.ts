@@ -0,0 +1,84 @@
+const completionSpec: Fig.Spec = {
+  name: "nested",
+  options: [
+    {
+      name: ["-C", "--cwd"],
+      description: "Change the working directory",
+      isPersistent: true,
+      args: {
+        name: "dir",
+        template: "folders",
+      },
+    },
+    {
+      name: "--debug",
+      isPersistent: true,
+    },
+  ],
+  subcommands: [
+    {
+      name: ["remote", "r"],
+      description: "Manage remotes",
+      options: [
+        {
+          name: ["-n", "--dry-run"],
+          description: "Don't do anything",
+          isPersistent: true,
+        },
+        {
+          name: ["-u", "--upstream"],
+          description: "Remote to track",
+          isPersistent: true,
+          isRequired: true,
+          args: {
+            name: "name",
+          },
+        },
+      ],
+      subcommands: [
+        {
+          name: "add",
+          description: "Add a remote",
+          args: [
+            {
+              name: "name",
+            },
+            {
+              name: "url",
+            },
+          ],
+        },
+        {
+          name: ["remove", "rm"],
+          description: "Remove remotes",
+          args: [
+            {
+              name: "names",
+              isVariadic: true,
+            },
+          ],
+        },
+      ],
+    },
+    {
+      name: "exec",
+      description: "Run a command",
+      args: [
+        {
+          name: "command",
+        },
+        {
+          name: "args",
+          isOptional: true,
+          isVariadic: true,
+        },
+      ],
+    },
+    {
+      name: "version",
+      description: "Print the version",
+    },
+  ],
+};
+
+export default completionSpec;
@@ -0,0 +1 @@
+require("arghelper") local matchers={} local function loop_until(word_index,line_state,user_data) if not user_data.first_index then user_data.first_index=word_index end local taken=word_index - user_data.first_index local prev_word=line_state:getword(word_index - 1) if (user_data.var_max >= 0 and taken >= user_data.var_max) or (prev_word == "--" and taken >= user_data.var_min) then return 1 end return 0 end local function delay_init(matcher,init) if matcher.setdelayinit then matcher:setdelayinit(init) else init(matcher) end return matcher end local function classify_args(matcher,args) if not matcher.setclassifier then return end local cmds={} for _,cmd in ipairs(args.cmds or {}) do cmds[cmd]=true end matcher:setclassifier(function(arg_index,word,word_index,line_state,classifications) local invalid if arg_index == 0 then return elseif arg_index == 1 and cmds[word] then invalid=args.required elseif args.max and arg_index > args.max then invalid=true elseif args[arg_index] then local typing=word_index == line_state:getwordcount() invalid=true for _,choice in ipairs(args[arg_index]) do if choice == word or (typing and choice:sub(1,#word) == word) then return end end end if invalid then classifications:classifyword(word_index,"n") return true end end) end function _flag_level() matchers._flag_level=matchers._flag_level or delay_init(clink.argmatcher(),function(matcher) matcher:_addexarg({"low","high",hint=[[Argument expected: <level>]]}) classify_args(matcher,{{"low","high"}}) end) return matchers._flag_level end local function flag_used(used,names) used=used or {} for name in names:gmatch("%S+") do if used[name] then return true end end return false end local used_global_flags={} local function line_flags(line_state) local line=line_state:getline() if used_global_flags.line ~= line then used_global_flags.line=line used_global_flags.names={} end return used_global_flags.names end local function hide_used(flags) local once={} local global={} local function scan(entries) for _,entry in ipairs(entries) do if type(entry) == "table" then if entry.global then for name in entry.global:gmatch("%S+") do global[name]=true end end if entry.once then for name in entry.once:gmatch("%S+") do once[name]=entry.once end else scan(entry) end end end end scan(flags) flags.onarg=function(arg_index,word,_,line_state,user_data) if arg_index == 0 then local name=(word:gsub("[:=].*$","")) user_data.used_flags=user_data.used_flags or {} user_data.used_flags[name]=true if global[name] then line_flags(line_state)[name]=true end end end table.insert(flags,function(_,_,line_state,_,user_data) clink.onfiltermatches(function(matches) local unused={} for _,match in ipairs(matches) do local names=once[match.match] local used=global[match.match] and line_flags(line_state) or user_data.used_flags if not (names and flag_used(used,names)) then table.insert(unused,match) end end return unused end) return {} end) return flags end local function cmd_hints(cmd) return function(hint) return function(arg_index,_,_,line_state,user_data) local parts={} if hint then table.insert(parts,hint) end if cmd.help then table.insert(parts,cmd.help) end local missing={} for _,names in ipairs(cmd.flags or {}) do if not flag_used(user_data.used_flags,names) then table.insert(missing,names:match("%-%-%S+") or names:match("%S+")) end end for _,names in ipairs(cmd.global_flags or {}) do if not flag_used(line_flags(line_state),names) then table.insert(missing,names:match("%-%-%S+") or names:match("%S+")) end end for i,arg in ipairs(cmd.args or {}) do if i > arg_index and arg then table.insert(missing,arg) end end if #missing > 0 then table.insert(parts,"Missing: " .. table.concat(missing," ")) end if #parts > 0 then return table.concat(parts," | ") end end end end function _flag_deploy__token() matchers._flag_deploy__token=matchers._flag_deploy__token or delay_init(clink.argmatcher(),function(matcher) matcher:_addexarg({hint=[[Argument expected: <token>]]}) end) return matchers._flag_deploy__token end function _cmd_deploy() matchers._cmd_deploy=matchers._cmd_deploy or delay_init(clink.argmatcher(),function(matcher) local hint=cmd_hints({help=[[Deploy to the env]],flags={"-t --token"}}) matcher:_addexflags(hide_used({{"-t" .. _flag_deploy__token()," <token>",[[Auth token]],once="-t --token"},{"--token" .. _flag_deploy__token()," <token>",[[Auth token]],once="-t --token"}})):_addexarg({hint=hint([=[Argument expected: <files>... [multiple args (0..2)]]=]),onadvance=function(_,_,wi,ls,ud) ud.var_min=0 ud.var_max=2 return loop_until(wi,ls,ud) end}) classify_args(matcher,{false,max=1}) end) return matchers._cmd_deploy end delay_init(clink.argmatcher("validate"),function(matcher) matcher:_addexflags(hide_used({{"--level" .. _flag_level()," <level>",once="--level"}})):_addexarg({"dev","prod",{"deploy" .. _cmd_deploy(),[[Deploy to the env]]},{"d" .. _cmd_deploy(),[[Deploy to the env]]},hint=[[Argument expected: <env>]]}):nofiles() classify_args(matcher,{{"dev","prod"},cmds={"deploy","d"},required=true}) end)
\ No newline at end of file
@@ -0,0 +1,42 @@
+const completionSpec: Fig.Spec = {
+  name: "validate",
+  options: [
+    {
+      name: "--level",
+      args: {
+        name: "level",
+        suggestions: ["low", "high"],
+      },
+    },
+  ],
+  subcommands: [
+    {
+      name: ["deploy", "d"],
+      description: "Deploy to the env",
+      options: [
+        {
+          name: ["-t", "--token"],
+          description: "Auth token",
+          isRequired: true,
+          args: {
+            name: "token",
+          },
+        },
+      ],
+      args: [
+        {
+          name: "files",
+          isVariadic: true,
+        },
+      ],
+    },
+  ],
+  args: [
+    {
+      name: "env",
+      suggestions: ["dev", "prod"],
+    },
+  ],
+};
+
+export default completionSpec;
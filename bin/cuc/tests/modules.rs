@@ -0,0 +1,71 @@
+//! Tests of `cuc modules`.
+
+mod common;
+
+use std::fs;
+
+use common::{run_cuc, temp_dir};
+
+const ARGHELPER: &str = include_str!("../../../lib/cuc-lib/assets/arghelper.lua");
+
+#[test]
+fn test_install_and_status() {
+    let dir = temp_dir("modules-install");
+    let dir_str = dir.to_str().unwrap();
+    let install = |force: bool| {
+        let mut args = vec!["modules", "install", "--dir", dir_str];
+        if force {
+            args.push("--force");
+        }
+        run_cuc(&args)
+    };
+    let status = || run_cuc(&["modules", "status", "--dir", dir_str]);
+
+    let (ok, stdout, _) = status();
+    assert!(!ok);
+    assert!(stdout.contains("missing"), "{}", stdout);
+
+    assert!(install(false).0);
+    assert_eq!(
+        fs::read_to_string(dir.join("arghelper.lua")).unwrap(),
+        ARGHELPER
+    );
+    let manifest = fs::read_to_string(dir.join(".cuc-modules")).unwrap();
+    assert!(manifest.starts_with("arghelper.lua="), "{}", manifest);
+    let (ok, stdout, _) = status();
+    assert!(ok);
+    assert!(stdout.contains("up to date"), "{}", stdout);
+
+    // Modified copies are reported and kept, unless forced
+    fs::write(dir.join("arghelper.lua"), "-- modified").unwrap();
+    let (ok, stdout, _) = status();
+    assert!(!ok);
+    assert!(stdout.contains("differs"), "{}", stdout);
+    let (ok, _, stderr) = install(false);
+    assert!(!ok);
+    assert!(stderr.contains("differs"), "{}", stderr);
+    assert!(install(true).0);
+    assert_eq!(
+        fs::read_to_string(dir.join("arghelper.lua")).unwrap(),
+        ARGHELPER
+    );
+
+    // Copies written by a newer cuc aren't downgraded, unless forced
+    fs::write(dir.join(".cuc-modules"), "arghelper.lua=999.0.0\n").unwrap();
+    let (ok, _, stderr) = install(false);
+    assert!(!ok);
+    assert!(stderr.contains("refusing to downgrade"), "{}", stderr);
+    assert!(install(true).0);
+    assert!(status().0);
+
+    // Copies written by an older cuc are upgraded
+    fs::write(dir.join(".cuc-modules"), "arghelper.lua=0.0.1\n").unwrap();
+    fs::write(dir.join("arghelper.lua"), "-- old").unwrap();
+    let (ok, stdout, _) = status();
+    assert!(!ok);
+    assert!(stdout.contains("outdated"), "{}", stdout);
+    assert!(install(false).0);
+    assert!(status().0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
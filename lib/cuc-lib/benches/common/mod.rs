@@ -0,0 +1,43 @@
+//! The synthetic spec shared by the benches.
+
+use cuc::usage::{Arg, Cmd, Flag, UsageSpec};
+
+fn flag(name: &str) -> Flag {
+    Flag {
+        name: name.to_string(),
+        names: vec![format!("--{}", name)],
+        help: format!("The {} flag", name),
+        ..Default::default()
+    }
+}
+
+fn cmd(name: &str, subcmds: usize) -> Cmd {
+    Cmd {
+        name: name.to_string(),
+        help: format!("The {} command", name),
+        flags: (0..5)
+            .map(|i| flag(&format!("{}-flag-{}", name, i)))
+            .collect(),
+        args: vec![Arg {
+            name: "value".to_string(),
+            repr: "<value>".to_string(),
+            choices: vec!["a".to_string(), "b".to_string()],
+            ..Default::default()
+        }],
+        cmds: (0..subcmds)
+            .map(|i| Box::new(cmd(&format!("{}-{}", name, i), 0)))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// A spec of `cmds` commands of `subcmds` subcommands each, with a few flags and an arg.
+pub fn spec(cmds: usize, subcmds: usize) -> UsageSpec {
+    let mut spec = UsageSpec::default();
+    spec.info.name = "bench".to_string();
+    spec.info.bin = "bench".to_string();
+    spec.cmds = (0..cmds)
+        .map(|i| cmd(&format!("cmd{}", i), subcmds))
+        .collect();
+    spec
+}
@@ -0,0 +1,41 @@
+//! Measures the generation of the completions of a large spec, from the spec to the written
+//! output, to track regressions.
+
+mod common;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use cuc::{
+    backend::{GenerateOptions, Registry, write_io},
+    graph::CompletionGraph,
+};
+
+fn bench_generate(c: &mut Criterion) {
+    // 10k commands
+    let spec = common::spec(1000, 9);
+    let registry = Registry::default();
+    let mut group = c.benchmark_group("generate");
+    for backend in registry.iter() {
+        let len = backend
+            .generate(&CompletionGraph::build(&spec), &GenerateOptions::default())
+            .unwrap()
+            .len();
+        group.throughput(Throughput::Bytes(len as u64));
+        // From the spec, as building the graph is part of every generation
+        group.bench_function(backend.name(), |b| {
+            b.iter(|| {
+                let graph = CompletionGraph::build(&spec);
+                write_io(
+                    backend,
+                    &graph,
+                    &GenerateOptions::default(),
+                    std::io::sink(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_generate);
+criterion_main!(benches);
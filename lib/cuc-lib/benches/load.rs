@@ -0,0 +1,83 @@
+//! Measures the load time of the generated clink script of a large spec, against the cost of
+//! building every argmatcher up front, which is what loading used to cost before they were
+//! built lazily.
+
+mod common;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use cuc::{
+    backend::{CompletionBackend, GenerateOptions, clink::ClinkBackend},
+    graph::CompletionGraph,
+};
+
+/// Just enough of clink and arghelper for the script to run.
+const CLINK_STUB: &str = r#"
+local argmatcher = {}
+argmatcher.__index = function(_, method)
+    if method == "setdelayinit" then
+        return function(self, init)
+            self.delayinit = init
+            return self
+        end
+    end
+    return function(self) return self end
+end
+argmatcher.__concat = function(word) return word end
+clink = { argmatchers = {} }
+function clink.argmatcher()
+    local matcher = setmetatable({}, argmatcher)
+    table.insert(clink.argmatchers, matcher)
+    return matcher
+end
+package.preload["arghelper"] = function() end
+
+function init_argmatchers()
+    local pending = true
+    while pending do
+        pending = false
+        for _, matcher in ipairs(clink.argmatchers) do
+            local init = rawget(matcher, "delayinit")
+            if init then
+                matcher.delayinit = nil
+                init(matcher)
+                pending = true
+            end
+        end
+    end
+end
+"#;
+
+fn bench_load(c: &mut Criterion) {
+    // 1100 commands
+    let graph = CompletionGraph::build(&common::spec(100, 10));
+    let script = ClinkBackend
+        .generate(&graph, &GenerateOptions::default())
+        .unwrap();
+    let lua = || {
+        let lua = mlua::Lua::new();
+        lua.load(CLINK_STUB).exec().unwrap();
+        lua
+    };
+
+    c.bench_function("load", |b| {
+        b.iter_batched(
+            lua,
+            |lua| lua.load(&script).exec().unwrap(),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("load and build every argmatcher", |b| {
+        b.iter_batched(
+            lua,
+            |lua| {
+                lua.load(&script).exec().unwrap();
+                lua.load("init_argmatchers()").exec().unwrap();
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_load);
+criterion_main!(benches);
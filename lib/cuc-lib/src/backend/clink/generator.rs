@@ -0,0 +1,1963 @@
+use std::{collections::HashSet, fmt};
+
+use super::{
+    ARGHELPER,
+    lua::{BinOp, Block, Expr, Field, Function, Printer, Stat, Style, UnOp},
+};
+use crate::{
+    backend::Completor,
+    graph::{
+        Candidates, CompletionGraph, FlagId, FlagNode, FlagTable, MatcherId, Position, Source,
+        SourceId,
+    },
+    mbase64, namespace,
+    usage::{CachePolicy, MatchType},
+};
+
+/// The generated functions, in the order they're generated so that the output is stable.
+#[derive(Default)]
+pub struct Functions<'me> {
+    names: HashSet<String>,
+    functions: Vec<(String, Stat)>,
+    /// Prints the functions as soon as they're added instead of keeping them, if set.
+    printer: Option<Printer<&'me mut dyn fmt::Write>>,
+    /// The first failure of the printer, reported once the functions are generated.
+    error: Option<fmt::Error>,
+}
+
+impl Functions<'_> {
+    pub fn contains(&self, func_name: &str) -> bool {
+        self.names.contains(func_name)
+    }
+
+    /// Adds the function, unless one of the same name was already added.
+    pub fn insert(&mut self, func_name: String, function: Stat) {
+        if !self.names.insert(func_name.clone()) {
+            return;
+        }
+        match self.printer {
+            Some(ref mut printer) if self.error.is_none() => {
+                self.error = printer.push(&function).err();
+            }
+            Some(_) => {}
+            None => self.functions.push((func_name, function)),
+        }
+    }
+
+    /// Takes the functions added since the last take. They stay known, so they aren't added
+    /// again but only referenced.
+    pub fn take(&mut self) -> Vec<Stat> {
+        std::mem::take(&mut self.functions)
+            .into_iter()
+            .map(|(_, function)| function)
+            .collect()
+    }
+
+    /// Takes the functions added since the last take, forgetting them so that they can be
+    /// added again, e.g. to another file.
+    pub fn forget(&mut self) -> Vec<Stat> {
+        for (func_name, _) in self.functions.iter() {
+            self.names.remove(func_name);
+        }
+        self.take()
+    }
+}
+
+pub struct GeneratorView<'me> {
+    pub graph: &'me CompletionGraph,
+    pub functions: Functions<'me>,
+    pub completor: Option<&'me Completor>,
+    pub arg_matchers: &'me [String],
+    /// Name of the local table holding the functions, which is returned by the script.
+    /// If none, the functions of the spec are global and the helpers local.
+    pub module: Option<String>,
+    /// Embed `arghelper.lua`, instead of requiring it from `package.path`.
+    pub standalone: bool,
+    /// Default of [`FlagNode::opteq`](crate::graph::FlagNode::opteq).
+    pub opteq: bool,
+    /// List the default of an arg as its first match, described as such.
+    pub suggest_defaults: bool,
+    /// Default of the `nosort` of the cmds and args of the spec, keeping the matches in order.
+    pub preserve_order: bool,
+    /// Default of [`Source::run_async`](crate::graph::Source::run_async).
+    pub async_complete: bool,
+    /// Default of [`Source::cache`](crate::graph::Source::cache).
+    pub cache: CachePolicy,
+    /// Dir of the files of the top-level cmds, relative to the root script, if split into files.
+    pub split: Option<String>,
+}
+
+impl<'me> GeneratorView<'me> {
+    pub fn generate(&mut self) -> Block {
+        let mut chunk = self.prelude();
+        let body = self.body();
+        chunk.extend(self.functions.take());
+        chunk.extend(body);
+        chunk
+    }
+
+    /// Prints the script to the sink as it's generated, each function as soon as it's added.
+    pub fn write(&mut self, style: Style, out: &'me mut dyn fmt::Write) -> fmt::Result {
+        let mut printer = Printer::new(style, out);
+        for stat in self.prelude() {
+            printer.push(&stat)?;
+        }
+        self.functions.printer = Some(printer);
+        let body = self.body();
+        let printer = self.functions.printer.take();
+        let mut printer = printer.expect("the printer is only taken here");
+        if let Some(err) = self.functions.error.take() {
+            return Err(err);
+        }
+        for stat in body.iter() {
+            printer.push(stat)?;
+        }
+        printer.finish()
+    }
+
+    /// The statements before the functions, which the functions rely on.
+    fn prelude(&self) -> Block {
+        let mut chunk = vec![];
+        if self.standalone {
+            chunk.push(Self::preload_arghelper());
+        }
+        chunk.push(Stat::Call(
+            Expr::name("require").call([Expr::str("arghelper")]),
+        ));
+        if let Some(ref module) = self.module {
+            chunk.push(Stat::local(module, Expr::table([])));
+        }
+        chunk.push(Stat::Comment(
+            "The argmatchers of the functions, built once on first call".into(),
+        ));
+        chunk.push(Stat::local("matchers", Expr::table([])));
+        if let Some(ref split) = self.split {
+            chunk.push(Self::split_dir(split));
+        }
+        chunk.push(self.loop_until_function());
+        chunk.push(self.delay_init_function());
+        chunk
+    }
+
+    /// The statements registering the argmatcher, after the functions it's built with.
+    fn body(&mut self) -> Block {
+        let bin = if self.arg_matchers.is_empty() {
+            vec![Expr::str(&self.graph.bin)]
+        } else {
+            vec![]
+        };
+        let matcher = Expr::name("clink").field("argmatcher").call(bin);
+        let init = self.init_matcher(self.graph.root());
+        let matcher = self.delay_init(matcher, init);
+
+        let mut script_body = vec![];
+        if self.arg_matchers.is_empty() {
+            script_body.push(Stat::Call(matcher));
+        } else {
+            script_body.push(Stat::local("matcher", matcher));
+            for arg_m in self.arg_matchers.iter() {
+                let register_parser = Expr::name("clink").field("arg").field("register_parser");
+                script_body.push(Stat::Call(
+                    register_parser.call([Expr::str(arg_m), Expr::name("matcher")]),
+                ));
+            }
+        }
+
+        if let Some(ref module) = self.module {
+            script_body.push(Stat::Return(Some(Expr::name(module))));
+        }
+        script_body
+    }
+
+    /// Generates the file of a top-level cmd, which is loaded by the root script on its first use.
+    ///
+    /// The file returns a function initializing the argmatcher of the cmd, along with the
+    /// functions of its subcmds and flags. The root functions are only referenced.
+    pub fn generate_cmd_file(&mut self, id: MatcherId) -> Block {
+        let body = self.init_matcher(id);
+
+        let mut init = vec![Stat::local("matchers", Expr::table([]))];
+        init.extend(self.functions.forget());
+        init.extend(body);
+        let params: Vec<&str> = self
+            .module
+            .iter()
+            .map(String::as_str)
+            .chain(["matcher"])
+            .collect();
+        vec![
+            Stat::Comment(format!(
+                "Completions of '{} {}', loaded on demand by {}.lua",
+                self.graph.bin,
+                self.graph.matcher(id).ns.display(),
+                self.split.as_deref().unwrap_or_default(),
+            )),
+            Stat::Return(Some(Expr::function(&params, init))),
+        ]
+    }
+
+    /// Name of the file of a top-level cmd, in the split dir.
+    pub fn cmd_file_name(&self, id: MatcherId) -> String {
+        format!("{}.lua", self.graph.matcher(id).ns.view().as_func_str())
+    }
+
+    /// The dir of the files of the top-level cmds, next to the root script.
+    fn split_dir(split: &str) -> Stat {
+        let source = Expr::name("debug")
+            .field("getinfo")
+            .call([Expr::Number(1), Expr::str("S")])
+            .field("source");
+        let script_dir = source
+            .method("match", [Expr::str(r"^@(.*[\/])")])
+            .binop(BinOp::Or, Expr::str(""))
+            .paren();
+        Stat::local(
+            "split_dir",
+            Expr::concat([script_dir, Expr::str(format!("{}/", split))]),
+        )
+    }
+
+    /// Preloads the embedded `arghelper.lua`, unless another script already did.
+    fn preload_arghelper() -> Stat {
+        let preload = Expr::name("package")
+            .field("preload")
+            .index(Expr::str("arghelper"));
+        let chunk = Expr::name("load").call([Expr::long_str(ARGHELPER), Expr::str("=arghelper")]);
+        Stat::Assign(preload.clone(), preload.binop(BinOp::Or, chunk))
+    }
+
+    /// The function as referenced by the script, i.e. a field of the module if any.
+    fn func(&self, func_name: &str) -> Expr {
+        match self.module {
+            Some(ref module) => Expr::name(module).field(func_name),
+            None => Expr::name(func_name),
+        }
+    }
+
+    fn define_func<P>(&self, func_name: &str, params: &[P], body: Block) -> Stat
+    where
+        P: AsRef<str>,
+    {
+        let name = match self.module {
+            Some(ref module) => format!("{}.{}", module, func_name),
+            None => func_name.to_string(),
+        };
+        Stat::function(name, params, body)
+    }
+
+    /// Defines a helper of the script, local unless kept in the module, so that the helpers of
+    /// another script (e.g. generated by another version) can't overwrite it.
+    fn define_helper<P>(&self, func_name: &str, params: &[P], body: Block) -> Stat
+    where
+        P: AsRef<str>,
+    {
+        match self.module {
+            Some(_) => self.define_func(func_name, params, body),
+            None => Stat::local_function(func_name, params, body),
+        }
+    }
+
+    /// Defines the table holding the state of some helpers, local unless kept in the module.
+    fn define_store(&self, name: &str) -> Stat {
+        match self.module {
+            Some(_) => Stat::Assign(self.func(name), Expr::table([])),
+            None => Stat::local(name, Expr::table([])),
+        }
+    }
+
+    /// Names of all the functions which can be generated, along with what they're generated for.
+    pub fn func_names(&self) -> Vec<(String, String)> {
+        let graph = self.graph;
+        let mut names = vec![];
+        for matcher in graph.matchers.iter() {
+            if !matcher.is_root() && !matcher.is_empty() {
+                let func_name = matcher.ns.view().matcher_func_name();
+                names.push((func_name, format!("cmd '{}'", matcher.ns)));
+            }
+        }
+        for flag in graph.flags.iter().filter(|f| f.arg.is_some()) {
+            let func_name = flag.ns.view().flag_func_name(&flag.name);
+            names.push((func_name, format!("flag '{}' of '{}'", flag.name, flag.ns)));
+        }
+        for table in graph.flag_tables.iter() {
+            let func_name = self.global_flags_func_name(table);
+            names.push((func_name, format!("global flags of '{}'", table.ns)));
+        }
+        for source in graph.sources.iter() {
+            let func_name = namespace::arg_complete_func_name(&source.name);
+            names.push((func_name, format!("complete '{}'", source.name)));
+        }
+        names
+    }
+
+    fn loop_until_function(&self) -> Stat {
+        let user_data = |field: &str| Expr::name("user_data").field(field);
+        let taken_at_least = |field: &str| Expr::name("taken").binop(BinOp::Ge, user_data(field));
+
+        let loop_cond = user_data("var_max")
+            .binop(BinOp::Ge, Expr::Number(0))
+            .binop(BinOp::And, taken_at_least("var_max"))
+            .paren()
+            .binop(
+                BinOp::Or,
+                Expr::name("prev_word")
+                    .binop(BinOp::Eq, Expr::str("--"))
+                    .binop(BinOp::And, taken_at_least("var_min"))
+                    .paren(),
+            );
+
+        let body = vec![
+            Stat::If(
+                vec![(
+                    Expr::unop(UnOp::Not, user_data("first_index")),
+                    vec![Stat::Assign(
+                        user_data("first_index"),
+                        Expr::name("word_index"),
+                    )],
+                )],
+                None,
+            ),
+            Stat::local(
+                "taken",
+                Expr::name("word_index").binop(BinOp::Sub, user_data("first_index")),
+            ),
+            Stat::local(
+                "prev_word",
+                Expr::name("line_state").method(
+                    "getword",
+                    [Expr::name("word_index").binop(BinOp::Sub, Expr::Number(1))],
+                ),
+            ),
+            Stat::Comment(
+                "Advance to the next arg position once var_max words were taken, or on '--'".into(),
+            ),
+            Stat::Comment("once var_min were. var_max is -1 to loop until '--'.".into()),
+            Stat::If(
+                vec![(loop_cond, vec![Stat::Return(Some(Expr::Number(1)))])],
+                None,
+            ),
+            Stat::Return(Some(Expr::Number(0))),
+        ];
+        self.define_helper(
+            "loop_until",
+            &["word_index", "line_state", "user_data"],
+            body,
+        )
+    }
+
+    /// Defers the init of the argmatcher until clink first uses it, if supported.
+    fn delay_init_function(&self) -> Stat {
+        let matcher = || Expr::name("matcher");
+        let init = || Expr::name("init");
+        let body = vec![
+            Stat::If(
+                vec![(
+                    matcher().field("setdelayinit"),
+                    vec![Stat::Call(matcher().method("setdelayinit", [init()]))],
+                )],
+                Some(vec![Stat::Call(init().call([matcher()]))]),
+            ),
+            Stat::Return(Some(matcher())),
+        ];
+        self.define_helper("delay_init", &["matcher", "init"], body)
+    }
+
+    /// Wraps the init of `matcher`, e.g. the calls chained on it, in an init function of the argmatcher.
+    fn delay_init(&self, matcher: Expr, body: Block) -> Expr {
+        let init = Expr::function(&["matcher"], body);
+        self.func("delay_init").call([matcher, init])
+    }
+
+    /// Body of a function returning the argmatcher, built on the first call only.
+    fn memoised_matcher(func_name: &str, matcher: Expr) -> Block {
+        let memo = Expr::name("matchers").field(func_name);
+        vec![
+            Stat::Assign(memo.clone(), memo.clone().binop(BinOp::Or, matcher)),
+            Stat::Return(Some(memo)),
+        ]
+    }
+
+    /// Wraps a table of `_addexflags`, recording the used flags and hiding the flags listing their
+    /// names in `once` from the matches once any of these names was used.
+    ///
+    /// The global flags, listed by the `global` of their table, are recorded for the whole line
+    /// by `line_flags`, as they're used before the subcmd whose argmatcher completes them.
+    fn hide_used_function(&self) -> Stat {
+        let once = || Expr::name("once");
+        let global = || Expr::name("global");
+        let entry = || Expr::name("entry");
+        let used = || Expr::name("user_data").field("used_flags");
+        let names_of = |list: Expr, body: Block| {
+            Stat::ForIn(
+                vec!["name".into()],
+                list.method("gmatch", [Expr::str("%S+")]),
+                body,
+            )
+        };
+        let ipairs = |name: &str, list: Expr, body: Block| {
+            Stat::ForIn(
+                vec!["_".into(), name.into()],
+                Expr::name("ipairs").call([list]),
+                body,
+            )
+        };
+        let table_insert = |list: &str, value: Expr| {
+            Stat::Call(
+                Expr::name("table")
+                    .field("insert")
+                    .call([Expr::name(list), value]),
+            )
+        };
+
+        let scan = Stat::Function {
+            local: true,
+            name: "scan".into(),
+            func: Function::new(
+                &["entries"],
+                vec![ipairs(
+                    "entry",
+                    Expr::name("entries"),
+                    vec![Stat::If(
+                        vec![(
+                            Expr::name("type")
+                                .call([entry()])
+                                .binop(BinOp::Eq, Expr::str("table")),
+                            vec![
+                                Stat::If(
+                                    vec![(
+                                        entry().field("global"),
+                                        vec![names_of(
+                                            entry().field("global"),
+                                            vec![Stat::Assign(
+                                                global().index(Expr::name("name")),
+                                                Expr::Bool(true),
+                                            )],
+                                        )],
+                                    )],
+                                    None,
+                                ),
+                                Stat::If(
+                                    vec![(
+                                        entry().field("once"),
+                                        vec![names_of(
+                                            entry().field("once"),
+                                            vec![Stat::Assign(
+                                                once().index(Expr::name("name")),
+                                                entry().field("once"),
+                                            )],
+                                        )],
+                                    )],
+                                    Some(vec![Stat::Call(Expr::name("scan").call([entry()]))]),
+                                ),
+                            ],
+                        )],
+                        None,
+                    )],
+                )],
+            ),
+        };
+
+        let line_flags = || self.func("line_flags").call([Expr::name("line_state")]);
+        let name = || Expr::name("name");
+        let onarg = Expr::function(
+            &["arg_index", "word", "_", "line_state", "user_data"],
+            vec![Stat::If(
+                vec![(
+                    Expr::name("arg_index").binop(BinOp::Eq, Expr::Number(0)),
+                    vec![
+                        Stat::local(
+                            "name",
+                            Expr::name("word")
+                                .method("gsub", [Expr::str("[:=].*$"), Expr::str("")])
+                                .paren(),
+                        ),
+                        Stat::Assign(used(), used().binop(BinOp::Or, Expr::table([]))),
+                        Stat::Assign(used().index(name()), Expr::Bool(true)),
+                        Stat::If(
+                            vec![(
+                                global().index(name()),
+                                vec![Stat::Assign(line_flags().index(name()), Expr::Bool(true))],
+                            )],
+                            None,
+                        ),
+                    ],
+                )],
+                None,
+            )],
+        );
+
+        let names = || Expr::name("names");
+        let filter = Expr::function(
+            &["matches"],
+            vec![
+                Stat::local("unused", Expr::table([])),
+                ipairs(
+                    "match",
+                    Expr::name("matches"),
+                    vec![
+                        Stat::local("names", once().index(Expr::name("match").field("match"))),
+                        Stat::local(
+                            "used",
+                            global()
+                                .index(Expr::name("match").field("match"))
+                                .binop(BinOp::And, line_flags())
+                                .binop(BinOp::Or, used()),
+                        ),
+                        Stat::If(
+                            vec![(
+                                Expr::unop(
+                                    UnOp::Not,
+                                    names()
+                                        .binop(
+                                            BinOp::And,
+                                            self.func("flag_used")
+                                                .call([Expr::name("used"), names()]),
+                                        )
+                                        .paren(),
+                                ),
+                                vec![table_insert("unused", Expr::name("match"))],
+                            )],
+                            None,
+                        ),
+                    ],
+                ),
+                Stat::Return(Some(Expr::name("unused"))),
+            ],
+        );
+        let hide_matches = Expr::function(
+            &["_", "_", "line_state", "_", "user_data"],
+            vec![
+                Stat::Call(Expr::name("clink").field("onfiltermatches").call([filter])),
+                Stat::Return(Some(Expr::table([]))),
+            ],
+        );
+
+        let body = vec![
+            Stat::local("once", Expr::table([])),
+            Stat::local("global", Expr::table([])),
+            scan,
+            Stat::Call(Expr::name("scan").call([Expr::name("flags")])),
+            Stat::Comment("The arg index is 0 for the flags, recorded for flag_used".into()),
+            Stat::Assign(Expr::name("flags").field("onarg"), onarg),
+            table_insert("flags", hide_matches),
+            Stat::Return(Some(Expr::name("flags"))),
+        ];
+        self.define_helper("hide_used", &["flags"], body)
+    }
+
+    /// Whether any of the names of a flag is in the used flags, as recorded by `hide_used`.
+    fn flag_used_function(&self) -> Stat {
+        let body = vec![
+            Stat::Assign(
+                Expr::name("used"),
+                Expr::name("used").binop(BinOp::Or, Expr::table([])),
+            ),
+            Stat::ForIn(
+                vec!["name".into()],
+                Expr::name("names").method("gmatch", [Expr::str("%S+")]),
+                vec![Stat::If(
+                    vec![(
+                        Expr::name("used").index(Expr::name("name")),
+                        vec![Stat::Return(Some(Expr::Bool(true)))],
+                    )],
+                    None,
+                )],
+            ),
+            Stat::Return(Some(Expr::Bool(false))),
+        ];
+        self.define_helper("flag_used", &["used", "names"], body)
+    }
+
+    /// The global flags used on the line, shared by the argmatchers of its cmds. Recorded anew
+    /// once the line changes.
+    fn line_flags_function(&self) -> Stat {
+        let store = || self.func("used_global_flags");
+        let line = || Expr::name("line");
+        let body = vec![
+            Stat::local("line", Expr::name("line_state").method("getline", [])),
+            Stat::If(
+                vec![(
+                    store().field("line").binop(BinOp::Ne, line()),
+                    vec![
+                        Stat::Assign(store().field("line"), line()),
+                        Stat::Assign(store().field("names"), Expr::table([])),
+                    ],
+                )],
+                None,
+            ),
+            Stat::Return(Some(store().field("names"))),
+        ];
+        self.define_helper("line_flags", &["line_state"], body)
+    }
+
+    fn used_global_flags_table(&self) -> Stat {
+        self.define_store("used_global_flags")
+    }
+
+    /// Adds `flag_used` and `line_flags`, along with its store.
+    fn insert_flag_used_functions(&mut self) {
+        let flag_used_function = self.flag_used_function();
+        self.functions
+            .insert("flag_used".into(), flag_used_function);
+        let used_global_flags_table = self.used_global_flags_table();
+        self.functions
+            .insert("used_global_flags".into(), used_global_flags_table);
+        let line_flags_function = self.line_flags_function();
+        self.functions
+            .insert("line_flags".into(), line_flags_function);
+    }
+
+    /// Makes the hint functions of the args of a cmd, adding the help of the cmd and the required
+    /// flags and args still missing to the hint of the arg, if any.
+    fn cmd_hints_function(&self) -> Stat {
+        let cmd = |field: &str| Expr::name("cmd").field(field);
+        let insert = |list: &str, value: Expr| {
+            Stat::Call(
+                Expr::name("table")
+                    .field("insert")
+                    .call([Expr::name(list), value]),
+            )
+        };
+        let names = || Expr::name("names");
+        let long_name = names()
+            .method("match", [Expr::str("%-%-%S+")])
+            .binop(BinOp::Or, names().method("match", [Expr::str("%S+")]));
+
+        // The global flags may be used before the cmd, so they're looked up in the whole line
+        let missing_flags = |list: &str, used: Expr| {
+            Stat::ForIn(
+                vec!["_".into(), "names".into()],
+                Expr::name("ipairs").call([cmd(list).binop(BinOp::Or, Expr::table([]))]),
+                vec![Stat::If(
+                    vec![(
+                        Expr::unop(UnOp::Not, self.func("flag_used").call([used, names()])),
+                        vec![insert("missing", long_name.clone())],
+                    )],
+                    None,
+                )],
+            )
+        };
+
+        let hint = Expr::function(
+            &["arg_index", "_", "_", "line_state", "user_data"],
+            vec![
+                Stat::local("parts", Expr::table([])),
+                Stat::If(
+                    vec![(
+                        Expr::name("hint"),
+                        vec![insert("parts", Expr::name("hint"))],
+                    )],
+                    None,
+                ),
+                Stat::If(
+                    vec![(cmd("help"), vec![insert("parts", cmd("help"))])],
+                    None,
+                ),
+                Stat::local("missing", Expr::table([])),
+                missing_flags("flags", Expr::name("user_data").field("used_flags")),
+                missing_flags(
+                    "global_flags",
+                    self.func("line_flags").call([Expr::name("line_state")]),
+                ),
+                Stat::Comment("The required args past the current one".into()),
+                Stat::ForIn(
+                    vec!["i".into(), "arg".into()],
+                    Expr::name("ipairs").call([cmd("args").binop(BinOp::Or, Expr::table([]))]),
+                    vec![Stat::If(
+                        vec![(
+                            Expr::name("i")
+                                .binop(BinOp::Gt, Expr::name("arg_index"))
+                                .binop(BinOp::And, Expr::name("arg")),
+                            vec![insert("missing", Expr::name("arg"))],
+                        )],
+                        None,
+                    )],
+                ),
+                Stat::If(
+                    vec![(
+                        Expr::unop(UnOp::Len, Expr::name("missing"))
+                            .binop(BinOp::Gt, Expr::Number(0)),
+                        vec![insert(
+                            "parts",
+                            Expr::concat([
+                                Expr::str("Missing: "),
+                                Expr::name("table")
+                                    .field("concat")
+                                    .call([Expr::name("missing"), Expr::str(" ")]),
+                            ]),
+                        )],
+                    )],
+                    None,
+                ),
+                Stat::If(
+                    vec![(
+                        Expr::unop(UnOp::Len, Expr::name("parts"))
+                            .binop(BinOp::Gt, Expr::Number(0)),
+                        vec![Stat::Return(Some(
+                            Expr::name("table")
+                                .field("concat")
+                                .call([Expr::name("parts"), Expr::str(" | ")]),
+                        ))],
+                    )],
+                    None,
+                ),
+            ],
+        );
+        let body = vec![Stat::Return(Some(Expr::function(
+            &["hint"],
+            vec![Stat::Return(Some(hint))],
+        )))];
+        self.define_helper("cmd_hints", &["cmd"], body)
+    }
+
+    /// Sets a classifier of the argmatcher, colouring the words which can't be valid as unexpected:
+    /// a value missing from the choices of its arg, a subcmd skipping the required first arg, or a
+    /// word past the `max` args. The word being typed only has to start a choice.
+    fn classify_args_function(&self) -> Stat {
+        let args = |field: &str| Expr::name("args").field(field);
+        let arg_index = || Expr::name("arg_index");
+        let word = || Expr::name("word");
+
+        let cmd_set = vec![
+            Stat::local("cmds", Expr::table([])),
+            Stat::ForIn(
+                vec!["_".into(), "cmd".into()],
+                Expr::name("ipairs").call([args("cmds").binop(BinOp::Or, Expr::table([]))]),
+                vec![Stat::Assign(
+                    Expr::name("cmds").index(Expr::name("cmd")),
+                    Expr::Bool(true),
+                )],
+            ),
+        ];
+
+        let typing = Expr::name("word_index").binop(
+            BinOp::Eq,
+            Expr::name("line_state").method("getwordcount", []),
+        );
+        let starts_choice = Expr::name("choice")
+            .method("sub", [Expr::Number(1), Expr::unop(UnOp::Len, word())])
+            .binop(BinOp::Eq, word());
+        let is_choice = Expr::name("choice").binop(BinOp::Eq, word()).binop(
+            BinOp::Or,
+            Expr::name("typing")
+                .binop(BinOp::And, starts_choice)
+                .paren(),
+        );
+        let check_choices = vec![
+            Stat::local("typing", typing),
+            Stat::Assign(Expr::name("invalid"), Expr::Bool(true)),
+            Stat::ForIn(
+                vec!["_".into(), "choice".into()],
+                Expr::name("ipairs").call([Expr::name("args").index(arg_index())]),
+                vec![Stat::If(vec![(is_choice, vec![Stat::Return(None)])], None)],
+            ),
+        ];
+
+        let classifier = Expr::function(
+            &[
+                "arg_index",
+                "word",
+                "word_index",
+                "line_state",
+                "classifications",
+            ],
+            vec![
+                Stat::Local("invalid".into(), None),
+                Stat::If(
+                    vec![
+                        (
+                            arg_index().binop(BinOp::Eq, Expr::Number(0)),
+                            vec![
+                                Stat::Comment("The flags are classified by clink".into()),
+                                Stat::Return(None),
+                            ],
+                        ),
+                        (
+                            arg_index()
+                                .binop(BinOp::Eq, Expr::Number(1))
+                                .binop(BinOp::And, Expr::name("cmds").index(word())),
+                            vec![Stat::Assign(Expr::name("invalid"), args("required"))],
+                        ),
+                        (
+                            args("max")
+                                .binop(BinOp::And, arg_index().binop(BinOp::Gt, args("max"))),
+                            vec![Stat::Assign(Expr::name("invalid"), Expr::Bool(true))],
+                        ),
+                        (Expr::name("args").index(arg_index()), check_choices),
+                    ],
+                    None,
+                ),
+                Stat::If(
+                    vec![(
+                        Expr::name("invalid"),
+                        vec![
+                            Stat::Call(Expr::name("classifications").method(
+                                "classifyword",
+                                [Expr::name("word_index"), Expr::str("n")],
+                            )),
+                            Stat::Return(Some(Expr::Bool(true))),
+                        ],
+                    )],
+                    None,
+                ),
+            ],
+        );
+
+        let mut body = vec![Stat::If(
+            vec![(
+                Expr::unop(UnOp::Not, Expr::name("matcher").field("setclassifier")),
+                vec![Stat::Return(None)],
+            )],
+            None,
+        )];
+        body.extend(cmd_set);
+        body.push(Stat::Call(
+            Expr::name("matcher").method("setclassifier", [classifier]),
+        ));
+        self.define_helper("classify_args", &["matcher", "args"], body)
+    }
+
+    /// Quotes an argument of the complete command, following the `CommandLineToArgvW` rules.
+    fn quote_arg_function(&self) -> Stat {
+        let gsub = |expr: Expr, pattern: &str, repl: &str| {
+            expr.method("gsub", [Expr::str(pattern), Expr::str(repl)])
+        };
+        let escaped = gsub(
+            gsub(Expr::name("arg"), r#"(\*)""#, r#"%1%1\""#),
+            r"(\+)$",
+            "%1%1",
+        );
+        let body = vec![
+            Stat::Comment("Backslashes are only special when followed by a quote".into()),
+            Stat::Assign(Expr::name("arg"), escaped),
+            Stat::Return(Some(Expr::concat([
+                Expr::str("\""),
+                Expr::name("arg"),
+                Expr::str("\""),
+            ]))),
+        ];
+        self.define_helper("quote_arg", &["arg"], body)
+    }
+
+    /// Inits the argmatcher of a cmd, i.e. chains its flags and args and sets its classifier.
+    fn init_matcher(&mut self, id: MatcherId) -> Block {
+        let node = self.graph.matcher(id);
+        let mut init: Block = self.add_cmd_hints(id).into_iter().collect();
+        let hinted = !init.is_empty();
+        let mut body = self.add_matcher_body(id, Expr::name("matcher"), hinted);
+        if node.is_root() {
+            body = body.method("nofiles", []);
+        }
+        init.push(Stat::Call(body));
+        init.extend(self.add_classifier(&node.positions, &node.subcmds));
+        init
+    }
+
+    /// Defines the `hint` local making the hints of the cmd, if there's more to them than the
+    /// expected arg.
+    ///
+    /// Format: `local hint = cmd_hints({ [help = [[HELP]]], [flags = { "NAMES", ... }], [global_flags = { "NAMES", ... }], [args = { "<ARG>" | false, ... }] })`
+    fn add_cmd_hints(&mut self, id: MatcherId) -> Option<Stat> {
+        let graph = self.graph;
+        let node = graph.matcher(id);
+        let mut fields = vec![];
+        if let Some(help) = node.help.lines().next()
+            && !help.is_empty()
+        {
+            fields.push(Field::named("help", Expr::long_str(help)));
+        }
+        let required_flags = |flags: &mut dyn Iterator<Item = &FlagId>| -> Vec<Field> {
+            flags
+                .map(|flag_id| graph.flag(*flag_id))
+                .filter(|flag| flag.required)
+                .map(|flag| Field::positional(Expr::str(Self::flag_names(flag))))
+                .collect()
+        };
+        let flags = required_flags(&mut node.flags.iter());
+        if !flags.is_empty() {
+            fields.push(Field::named("flags", Expr::table(flags)));
+        }
+        let global_flags = required_flags(
+            &mut graph
+                .global_flag_tables(id)
+                .flat_map(|t| &graph.flag_table(t).flags),
+        );
+        if !global_flags.is_empty() {
+            fields.push(Field::named("global_flags", Expr::table(global_flags)));
+        }
+        // The first required arg is already expected by the hint of its position
+        if node.positions.iter().filter(|arg| arg.required).count() > 1 {
+            let args = node.positions.iter().map(|arg| match arg.required {
+                true => Field::positional(Expr::str(&arg.repr)),
+                false => Field::positional(Expr::Bool(false)),
+            });
+            fields.push(Field::named("args", Expr::table(args)));
+        }
+        if fields.is_empty() {
+            return None;
+        }
+
+        self.insert_flag_used_functions();
+        let cmd_hints_function = self.cmd_hints_function();
+        self.functions
+            .insert("cmd_hints".into(), cmd_hints_function);
+        Some(Stat::local(
+            "hint",
+            self.func("cmd_hints").call([Expr::table(fields)]),
+        ))
+    }
+
+    /// Colours the invalid args of the argmatcher, if any can be invalid.
+    ///
+    /// Format: `classify_args(matcher, { { "CHOICE", ... } | false, ..., [max = N], [cmds = { "CMD", ... }], [required = true] })`
+    fn add_classifier(&mut self, positions: &[Position], subcmds: &[MatcherId]) -> Option<Stat> {
+        let graph = self.graph;
+        let mut fields = vec![];
+        let mut any_invalid = false;
+        for arg in positions {
+            match arg.candidates {
+                Candidates::Choices(ref choices) if !choices.is_empty() => {
+                    any_invalid = true;
+                    fields.push(Field::positional(Expr::table(
+                        choices.iter().map(|c| Field::positional(Expr::str(c))),
+                    )));
+                }
+                _ => fields.push(Field::positional(Expr::Bool(false))),
+            }
+        }
+        // The values past a bounded variadic last arg are too many
+        if let Some((_, var_max)) = positions.last().and_then(|arg| arg.var)
+            && var_max >= 0
+        {
+            any_invalid = true;
+            let max = positions.len() as i128;
+            fields.push(Field::named("max", Expr::Number(max)));
+        }
+        // The subcmds are completed along with the first arg, skipping it if required
+        let skips_required = positions.first().is_some_and(|arg| arg.required);
+        if !subcmds.is_empty() && (any_invalid || skips_required) {
+            any_invalid = true;
+            let cmds = subcmds
+                .iter()
+                .flat_map(|id| &graph.matcher(*id).words)
+                .map(|word| Field::positional(Expr::str(&word.name)));
+            fields.push(Field::named("cmds", Expr::table(cmds)));
+            if skips_required {
+                fields.push(Field::named("required", Expr::Bool(true)));
+            }
+        }
+        if !any_invalid {
+            return None;
+        }
+
+        let classify_args_function = self.classify_args_function();
+        self.functions
+            .insert("classify_args".into(), classify_args_function);
+        Some(Stat::Call(
+            self.func("classify_args")
+                .call([Expr::name("matcher"), Expr::table(fields)]),
+        ))
+    }
+
+    /// Chains the flags and args of the matcher to the argmatcher.
+    ///
+    /// If `hinted`, the hints are made by the `hint` local, see [`Self::add_cmd_hints`].
+    fn add_matcher_body(&mut self, id: MatcherId, matcher: Expr, hinted: bool) -> Expr {
+        let matcher = self.add_flags(id, matcher, hinted);
+        self.add_args_and_cmds(id, matcher, hinted)
+    }
+
+    fn add_flags(&mut self, id: MatcherId, matcher: Expr, hinted: bool) -> Expr {
+        let graph = self.graph;
+        let node = graph.matcher(id);
+        // Generate functions of returning anonymous clink.argmatcher
+        // to link them to the corresponding flag
+        self.generate_flag_functions(&node.flags);
+        // Along with its own global flags, the ones left by the shadowing of the ancestors' ones
+        for table_id in graph.global_flag_tables(id) {
+            let table = graph.flag_table(table_id);
+            if table.shadowed || node.global_flags == Some(table_id) {
+                self.generate_global_flags_function(table);
+            }
+        }
+
+        let mut entries: Vec<Field> = vec![];
+        for flag_id in node.flags.iter() {
+            entries.extend(self.add_flag_body(*flag_id));
+        }
+
+        // Now, add the global flag funcs containing the flags of the ancestors and itself.
+        for table_id in graph.global_flag_tables(id) {
+            let func_name = self.global_flags_func_name(graph.flag_table(table_id));
+            entries.push(Field::positional(self.func(&func_name).call([])));
+        }
+
+        if entries.is_empty() {
+            return matcher;
+        }
+        // Without any arg, the hints of the cmd are shown along with its flags
+        if hinted && node.positions.is_empty() && node.subcmds.is_empty() {
+            entries.push(Field::named("hint", Expr::name("hint").call([])));
+        }
+        entries.extend(self.add_nosort(node.nosort.unwrap_or(self.preserve_order)));
+        let mut flags = Expr::table(entries);
+        let record_used = node
+            .flags
+            .iter()
+            .chain(
+                graph
+                    .global_flag_tables(id)
+                    .flat_map(|t| &graph.flag_table(t).flags),
+            )
+            .map(|flag_id| graph.flag(*flag_id))
+            .any(|flag| !flag.repeatable || flag.required);
+        if record_used {
+            self.insert_flag_used_functions();
+            let hide_used_function = self.hide_used_function();
+            self.functions
+                .insert("hide_used".into(), hide_used_function);
+            flags = self.func("hide_used").call([flags]);
+        }
+        matcher.method("_addexflags", [flags])
+    }
+
+    /// Returns an entry per flag name and alias.
+    ///
+    /// Format: `{ "FLAG" .. _flag_FUNC_NAME(), [" ARG_INFO"], [" FLAG_HELP"], [opteq = true], [once = "NAMES"] }`
+    ///
+    /// The flags which can't repeat list all their names in `once`, see [`Self::hide_used_function`].
+    fn add_flag_body(&self, id: FlagId) -> Vec<Field> {
+        let flag = self.graph.flag(id);
+        let func_name = flag.ns.view().flag_func_name(&flag.name);
+        let once = Self::flag_names(flag);
+        let mut entries = vec![];
+        for word in flag.words.iter() {
+            let mut entry = vec![];
+            if let Some(ref arg) = flag.arg {
+                let flag_matcher = self.func(&func_name).call([]);
+                entry.push(Field::positional(Expr::concat([
+                    Expr::str(&word.name),
+                    flag_matcher,
+                ])));
+                entry.push(Field::positional(Expr::str(format!(" {}", arg.repr))));
+            } else {
+                entry.push(Field::positional(Expr::str(&word.name)));
+            }
+
+            if !flag.help.is_empty() {
+                entry.push(Field::positional(Expr::long_str(&flag.help)));
+            }
+            if flag.arg.is_some() && word.name.starts_with("--") && flag.opteq.unwrap_or(self.opteq)
+            {
+                // arghelper adds a hidden `--flag=` linked to the same matcher
+                entry.push(Field::named("opteq", Expr::Bool(true)));
+            }
+            entry.extend(Self::add_hide(word.hidden));
+            if !flag.repeatable {
+                entry.push(Field::named("once", Expr::str(&once)));
+            }
+
+            let entry = Field::positional(Expr::table(entry));
+            entries.push(match word.alias {
+                true => entry.with_comment("alias"),
+                false => entry,
+            });
+        }
+        entries
+    }
+
+    /// The names and aliases of the flag, separated by spaces.
+    fn flag_names(flag: &FlagNode) -> String {
+        let names: Vec<&str> = flag.words.iter().map(|w| w.name.as_str()).collect();
+        names.join(" ")
+    }
+
+    /// Keeps the matches of the table in order, instead of sorting them.
+    fn add_nosort(&self, nosort: bool) -> Option<Field> {
+        nosort.then(|| Field::named("nosort", Expr::Bool(true)))
+    }
+
+    /// Hides the match from the list, while still matching it when typed.
+    fn add_hide(hidden: bool) -> Option<Field> {
+        hidden.then(|| Field::named("hide", Expr::Bool(true)))
+    }
+
+    /// The hint of the arg, made by the `hint` local if `hinted`.
+    fn add_arg_hint(arg: &Position, hinted: bool) -> Field {
+        let mut hint = String::from("Argument expected: ");
+        hint += &arg.repr;
+        if let Some((var_min, var_max)) = arg.var {
+            let var_max = if var_max < 0 {
+                "*".into()
+            } else {
+                var_max.to_string()
+            };
+            hint += &format!(" [multiple args ({}..{})]", var_min, var_max);
+        }
+        if let Some(ref default) = arg.default {
+            hint += &format!(" [default: {}]", default);
+        }
+        match hinted {
+            true => Field::named("hint", Expr::name("hint").call([Expr::long_str(hint)])),
+            false => Field::named("hint", Expr::long_str(hint)),
+        }
+    }
+
+    fn add_arg_loop_until(&self, arg: &Position) -> Option<Field> {
+        let (var_min, var_max) = arg.var?;
+        // Instead of using :loop(), use onadvance to advance through the argument positions.
+        // This is because one can't break out of loop to the next arg position.
+        let user_data = |field: &str| Expr::name("ud").field(field);
+        let onadvance = Expr::function(
+            &["_", "_", "wi", "ls", "ud"],
+            vec![
+                Stat::Assign(user_data("var_min"), Expr::Number(var_min)),
+                Stat::Assign(user_data("var_max"), Expr::Number(var_max)),
+                Stat::Return(Some(self.func("loop_until").call([
+                    Expr::name("wi"),
+                    Expr::name("ls"),
+                    Expr::name("ud"),
+                ]))),
+            ],
+        );
+        Some(Field::named("onadvance", onadvance))
+    }
+
+    /// Returns the candidates of the arg, to be added as entries of `_addexarg`.
+    fn add_arg_candidates(&mut self, arg: &Position) -> Vec<Field> {
+        if arg.hidden {
+            // Only the choices can be hidden, the other candidates aren't listed at all
+            let Candidates::Choices(ref choices) = arg.candidates else {
+                return vec![];
+            };
+            return choices
+                .iter()
+                .map(|choice| {
+                    let hide = Field::named("hide", Expr::Bool(true));
+                    Field::positional(Expr::table([Field::positional(Expr::str(choice)), hide]))
+                })
+                .collect();
+        }
+        let default = arg.default.as_ref().filter(|_| self.suggest_defaults);
+        let mut entries = match default {
+            Some(default) => vec![Field::positional(Expr::table([
+                Field::positional(Expr::str(default)),
+                Field::positional(Expr::str("default")),
+            ]))],
+            None => vec![],
+        };
+        entries.extend(match arg.candidates {
+            Candidates::Choices(ref choices) => choices
+                .iter()
+                .filter(|choice| Some(*choice) != default)
+                .map(|choice| Field::positional(Expr::str(choice)))
+                .collect(),
+            Candidates::Files => vec![Field::positional(Expr::name("clink").field("filematches"))],
+            Candidates::Dirs => vec![Field::positional(Expr::name("clink").field("dirmatches"))],
+            Candidates::Run(source_id) if self.completor.is_some() => {
+                let func_name = self.generate_arg_complete_function(source_id);
+                vec![Field::positional(self.func(&func_name))]
+            }
+            _ => vec![],
+        });
+        entries
+    }
+
+    /// Returns the whole `_addexarg` table of an arg position.
+    ///
+    /// The matches are sorted unless the arg, else the cmd of the arg (`cmd_nosort`), says otherwise.
+    fn add_arg(
+        &mut self,
+        arg: &Position,
+        cmds: Vec<Field>,
+        hinted: bool,
+        cmd_nosort: Option<bool>,
+    ) -> Expr {
+        let mut entries = self.add_arg_candidates(arg);
+        let has_cmds = !cmds.is_empty();
+        entries.extend(cmds);
+        if !arg.hidden {
+            let hint = Self::add_arg_hint(arg, hinted);
+            entries.push(self.add_loading_hint(arg, hint));
+        }
+        // The suggested default is kept first among the choices, the files, dirs, dynamic
+        // completions and cmds are still sorted
+        let default_first = self.suggest_defaults
+            && arg.default.is_some()
+            && !arg.hidden
+            && matches!(arg.candidates, Candidates::Choices(_))
+            && !has_cmds;
+        let nosort = arg.nosort.or(cmd_nosort).unwrap_or(self.preserve_order);
+        entries.extend(self.add_nosort(nosort || default_first));
+        entries.extend(self.add_arg_loop_until(arg));
+        Expr::table(entries)
+    }
+
+    /// Chains the arg positions of the matcher. The subcmds are completed along with the first one.
+    fn add_args_and_cmds(&mut self, id: MatcherId, mut matcher: Expr, hinted: bool) -> Expr {
+        let node = self.graph.matcher(id);
+        // Generate functions to be linked with subcmds
+        self.generate_cmd_functions(&node.subcmds);
+
+        let mut cmds: Vec<Field> = vec![];
+        for subcmd_id in node.subcmds.iter() {
+            cmds.extend(self.add_cmd_body(*subcmd_id));
+        }
+
+        match node.positions.first() {
+            Some(arg) => {
+                let entries = self.add_arg(arg, cmds, hinted, node.nosort);
+                matcher = matcher.method("_addexarg", [entries]);
+            }
+            None if !cmds.is_empty() => {
+                if hinted {
+                    cmds.push(Field::named("hint", Expr::name("hint").call([])));
+                }
+                cmds.extend(self.add_nosort(node.nosort.unwrap_or(self.preserve_order)));
+                matcher = matcher.method("_addexarg", [Expr::table(cmds)]);
+            }
+            None => {}
+        }
+
+        for arg in node.positions.iter().skip(1) {
+            let entries = self.add_arg(arg, vec![], hinted, node.nosort);
+            matcher = matcher.method("_addexarg", [entries]);
+        }
+        matcher
+    }
+
+    /// Returns an entry per cmd name and alias.
+    fn add_cmd_body(&self, id: MatcherId) -> Vec<Field> {
+        let node = self.graph.matcher(id);
+        let func_name = node.ns.view().matcher_func_name();
+        let mut entries = vec![];
+        for word in node.words.iter() {
+            let mut entry = vec![];
+            if !node.is_empty() {
+                let cmd_matcher = self.func(&func_name).call([]);
+                entry.push(Field::positional(Expr::concat([
+                    Expr::str(&word.name),
+                    cmd_matcher,
+                ])));
+            } else {
+                entry.push(Field::positional(Expr::str(&word.name)));
+            }
+            if !node.help.is_empty() {
+                entry.push(Field::positional(Expr::long_str(&node.help)));
+            }
+            entry.extend(Self::add_hide(word.hidden));
+
+            let entry = Field::positional(Expr::table(entry));
+            entries.push(match word.alias {
+                true => entry.with_comment("alias"),
+                false => entry,
+            });
+        }
+        entries
+    }
+
+    fn generate_flag_functions(&mut self, flags: &[FlagId]) {
+        for flag_id in flags.iter() {
+            let flag = self.graph.flag(*flag_id);
+            let Some(ref arg) = flag.arg else {
+                continue;
+            };
+
+            let func_name = flag.ns.view().flag_func_name(&flag.name);
+            let entries = self.add_arg(arg, vec![], false, None);
+            let mut body = vec![Stat::Call(
+                Expr::name("matcher").method("_addexarg", [entries]),
+            )];
+            body.extend(self.add_classifier(std::slice::from_ref(arg), &[]));
+            let matcher = Expr::name("clink").field("argmatcher").call([]);
+            let matcher = self.delay_init(matcher, body);
+            let function = self.define_func(
+                &func_name,
+                &[] as &[&str],
+                Self::memoised_matcher(&func_name, matcher),
+            );
+            self.functions.insert(func_name, function);
+        }
+    }
+
+    /// Name of the function returning the flags of the table, told apart by their words if some
+    /// are shadowed.
+    fn global_flags_func_name(&self, table: &FlagTable) -> String {
+        if !table.shadowed {
+            return table.ns.view().global_flag_func_name();
+        }
+        let words: Vec<&str> = table
+            .flags
+            .iter()
+            .flat_map(|flag_id| self.graph.flag(*flag_id).words.iter())
+            .map(|word| word.name.as_str())
+            .collect();
+        table.ns.view().shadowed_global_flag_func_name(&words)
+    }
+
+    fn generate_global_flags_function(&mut self, table: &FlagTable) {
+        let flags = &table.flags;
+        self.generate_flag_functions(flags);
+
+        let mut entries = vec![];
+        for flag_id in flags.iter() {
+            entries.extend(self.add_flag_body(*flag_id));
+        }
+
+        // Lists the names of the global flags, for hide_used to record them for the whole line
+        let names: Vec<String> = flags
+            .iter()
+            .map(|flag_id| Self::flag_names(self.graph.flag(*flag_id)))
+            .collect();
+        entries.push(Field::named("global", Expr::str(names.join(" "))));
+
+        let func_name = self.global_flags_func_name(table);
+        let function = self.define_func(
+            &func_name,
+            &[] as &[&str],
+            vec![Stat::Return(Some(Expr::table(entries)))],
+        );
+        self.functions.insert(func_name, function);
+    }
+
+    fn generate_cmd_functions(&mut self, cmds: &[MatcherId]) {
+        for cmd_id in cmds.iter() {
+            let node = self.graph.matcher(*cmd_id);
+            if node.is_empty() {
+                continue;
+            }
+
+            let func_name = node.ns.view().matcher_func_name();
+            let body = match self.split {
+                Some(_) if node.ns.parent().is_root() => {
+                    vec![Stat::Call(self.load_cmd_file(*cmd_id))]
+                }
+                _ => self.init_matcher(*cmd_id),
+            };
+            let matcher = Expr::name("clink").field("argmatcher").call([]);
+            let matcher = self.delay_init(matcher, body);
+            let function = self.define_func(
+                &func_name,
+                &[] as &[&str],
+                Self::memoised_matcher(&func_name, matcher),
+            );
+            self.functions.insert(func_name, function);
+        }
+    }
+
+    /// Loads the file of the top-level cmd, initializing the argmatcher.
+    fn load_cmd_file(&self, id: MatcherId) -> Expr {
+        let path = Expr::concat([Expr::name("split_dir"), Expr::str(self.cmd_file_name(id))]);
+        let mut args: Vec<Expr> = self.module.iter().map(Expr::name).collect();
+        args.push(Expr::name("matcher"));
+        Expr::name("dofile").call([path]).call(args)
+    }
+
+    /// Returns the name of the function, generating it on first use.
+    fn generate_arg_complete_function(&mut self, id: SourceId) -> String {
+        let completor = self
+            .completor
+            .expect("No completor! Can't generate arg completions without it");
+        let source = self.graph.source(id);
+        let func_name = namespace::arg_complete_func_name(&source.name);
+        if self.functions.contains(&func_name) {
+            return func_name;
+        }
+
+        let encoded_script = mbase64::encode(&source.run);
+        let quote_arg_func = self.func("quote_arg");
+        let quote_arg = |arg: &str| quote_arg_func.clone().call([Expr::name(arg)]);
+        // The cache args are plain words, e.g. `--cache-ttl 30`, so they're kept in the literal
+        let mut script_sep: String = completor
+            .cache_args(source)
+            .iter()
+            .map(|arg| format!(" {}", arg))
+            .collect();
+        script_sep += " -- ";
+        let args = Expr::concat([
+            Expr::str(" complete --current "),
+            Expr::name("word_index").binop(BinOp::Sub, Expr::Number(1)),
+            Expr::str(" --line-hex "),
+            Expr::name("line_hex"),
+            Expr::str(" --shell "),
+            quote_arg("shell"),
+            Expr::str(script_sep),
+            Expr::name("b64_encoded_script"),
+        ]);
+        let hex_byte = Expr::function(
+            &["c"],
+            vec![Stat::Return(Some(
+                Expr::name("string")
+                    .field("format")
+                    .call([Expr::str("%02x"), Expr::name("c").method("byte", [])]),
+            ))],
+        );
+        let quote_arg_function = self.quote_arg_function();
+        self.functions
+            .insert("quote_arg".into(), quote_arg_function);
+
+        let mut body = vec![
+            Stat::Comment(source.run.clone()),
+            Stat::local("b64_encoded_script", Expr::long_str(encoded_script)),
+            Stat::local(
+                "exec",
+                Expr::long_str(completor.exe_path.display().to_string()),
+            ),
+            Stat::local(
+                "shell",
+                Expr::long_str(completor.shell.display().to_string()),
+            ),
+            Stat::Comment("The line is hex encoded, so that cmd.exe can't mangle it".into()),
+            Stat::local(
+                "line_hex",
+                Expr::name("line_state")
+                    .method("getline", [])
+                    .method("gsub", [Expr::str("."), hex_byte]),
+            ),
+            Stat::local("args", args),
+            Stat::Comment("cmd.exe strips the outer quotes, leaving the quoted exec as is".into()),
+            Stat::local(
+                "command",
+                Expr::concat([
+                    Expr::str("\""),
+                    quote_arg("exec"),
+                    Expr::name("args"),
+                    Expr::str("\""),
+                ]),
+            ),
+        ];
+        let command = Expr::name("command");
+        let run_async = source.run_async.unwrap_or(self.async_complete);
+        let cache = source.cache.unwrap_or(self.cache);
+        let (names, lines) = match (run_async, cache) {
+            (false, CachePolicy::Off) => {
+                body.extend([
+                    Stat::local("pipe", Expr::name("io").field("popen").call([command])),
+                    Stat::Call(Expr::name("assert").call([
+                        Expr::name("pipe"),
+                        Expr::str("[ERROR]: failed to run complete command"),
+                    ])),
+                ]);
+                (vec!["line".into()], Expr::name("pipe").method("lines", []))
+            }
+            _ => {
+                body.extend(self.add_lines(id, command, run_async, cache));
+                (
+                    vec!["_".into(), "line".into()],
+                    Expr::name("ipairs").call([Expr::name("lines")]),
+                )
+            }
+        };
+        // Typed matches are added to the match builder instead of being returned
+        let for_body = match source.match_type {
+            Some(match_type) => self.add_typed_match(source.descs, match_type),
+            None => {
+                body.push(Stat::local("complete_args", Expr::table([])));
+                Self::add_plain_match(source.descs)
+            }
+        };
+        let piped = !run_async && cache == CachePolicy::Off;
+        body.push(Stat::ForIn(names, lines, for_body));
+        if piped {
+            body.push(Stat::Call(Expr::name("pipe").method("close", [])));
+        }
+        body.push(Stat::Return(Some(match source.match_type {
+            Some(_) => Expr::table([]),
+            None => Expr::name("complete_args"),
+        })));
+        let function = self.define_func(
+            &func_name,
+            &[
+                "word",
+                "word_index",
+                "line_state",
+                "match_builder",
+                "user_data",
+            ],
+            body,
+        );
+
+        self.functions.insert(func_name.clone(), function);
+        func_name
+    }
+
+    /// Gets the output lines of the command, reused from the cache if any. When run in the
+    /// background, no matches are returned while they're loading.
+    fn add_lines(
+        &mut self,
+        id: SourceId,
+        command: Expr,
+        run_async: bool,
+        cache: CachePolicy,
+    ) -> Block {
+        let source = self.graph.source(id);
+        self.functions
+            .insert("read_lines".into(), self.read_lines_function());
+        let mut body = Self::add_cache_key(source);
+        let name = || Expr::str(&source.name);
+        let key = || Expr::name("key");
+        let cached = match cache {
+            CachePolicy::Off => None,
+            CachePolicy::Session | CachePolicy::Ttl(_) => {
+                self.functions
+                    .insert("completion_cache".into(), self.completion_cache_table());
+                self.functions
+                    .insert("cached_lines".into(), self.cached_lines_function());
+                self.functions
+                    .insert("cache_lines".into(), self.cache_lines_function());
+                let ttl = match cache {
+                    CachePolicy::Ttl(secs) => Expr::Number(secs.into()),
+                    _ => Expr::Nil,
+                };
+                Some(self.func("cached_lines").call([name(), key(), ttl]))
+            }
+        };
+        if !run_async {
+            let load = self
+                .func("read_lines")
+                .call([Expr::name("io").field("popen").call([command])]);
+            let Some(cached) = cached else {
+                body.push(Stat::local("lines", load));
+                return body;
+            };
+            body.extend([
+                Stat::local("lines", cached),
+                Stat::If(
+                    vec![(
+                        Expr::unop(UnOp::Not, Expr::name("lines")),
+                        vec![
+                            Stat::Assign(Expr::name("lines"), load),
+                            Stat::Call(self.func("cache_lines").call([
+                                name(),
+                                key(),
+                                Expr::name("lines"),
+                            ])),
+                        ],
+                    )],
+                    None,
+                ),
+            ]);
+            return body;
+        }
+
+        self.functions
+            .insert("async_completion".into(), self.async_completion_table());
+        self.functions
+            .insert("complete_async".into(), self.complete_async_function());
+        let mut args = vec![
+            name(),
+            key(),
+            Expr::name("line_state").method("getline", []),
+            command,
+        ];
+        if cached.is_some() {
+            args.push(self.func("cache_lines"));
+        }
+        let load = self.func("complete_async").call(args);
+        let lines = match cached {
+            Some(cached) => cached.binop(BinOp::Or, load),
+            None => load,
+        };
+        body.push(Stat::local("lines", lines));
+        let set_volatile = Expr::name("match_builder").field("setvolatile");
+        body.push(Stat::If(
+            vec![(
+                Expr::unop(UnOp::Not, Expr::name("lines")),
+                vec![
+                    Stat::Comment("Completed again once loaded".into()),
+                    Stat::If(
+                        vec![(
+                            set_volatile,
+                            vec![Stat::Call(
+                                Expr::name("match_builder").method("setvolatile", []),
+                            )],
+                        )],
+                        None,
+                    ),
+                    Stat::Return(Some(Expr::table([]))),
+                ],
+            )],
+            None,
+        ));
+        body
+    }
+
+    /// The inputs of the output of the completer, i.e. the args of `cuc complete` which it renders
+    /// the run from (the line, the index of the word being completed and the template), the cwd
+    /// and the cache env vars.
+    fn add_cache_key(source: &Source) -> Block {
+        let mut parts = vec![
+            Field::positional(Expr::name("args")),
+            Field::positional(Expr::name("os").field("getcwd").call([])),
+        ];
+        for name in source.cache_env.iter() {
+            let value = Expr::name("os").field("getenv").call([Expr::str(name)]);
+            parts.push(Field::positional(value.binop(BinOp::Or, Expr::str(""))));
+        }
+        vec![Stat::local(
+            "key",
+            Expr::name("table")
+                .field("concat")
+                .call([Expr::table(parts), Expr::str("\0")]),
+        )]
+    }
+
+    /// The cached output lines of the completers, by completer and by the inputs of its run.
+    fn completion_cache_table(&self) -> Stat {
+        self.define_store("completion_cache")
+    }
+
+    /// Returns the cached lines of the completer for the key, unless older than the ttl (in
+    /// seconds, none for the whole session).
+    fn cached_lines_function(&self) -> Stat {
+        let cached = || Expr::name("cached");
+        let now = || Expr::name("os").field("time").call([]);
+        let body = vec![
+            Stat::local(
+                "cached",
+                self.func("completion_cache")
+                    .index(Expr::name("name"))
+                    .binop(
+                        BinOp::And,
+                        self.func("completion_cache")
+                            .index(Expr::name("name"))
+                            .index(Expr::name("key")),
+                    ),
+            ),
+            Stat::If(
+                vec![(
+                    cached().binop(
+                        BinOp::And,
+                        Expr::unop(UnOp::Not, Expr::name("ttl"))
+                            .binop(
+                                BinOp::Or,
+                                now()
+                                    .binop(BinOp::Sub, cached().field("time"))
+                                    .binop(BinOp::Lt, Expr::name("ttl")),
+                            )
+                            .paren(),
+                    ),
+                    vec![Stat::Return(Some(cached().field("lines")))],
+                )],
+                None,
+            ),
+        ];
+        self.define_helper("cached_lines", &["name", "key", "ttl"], body)
+    }
+
+    /// Caches the output lines of the completer for the key.
+    fn cache_lines_function(&self) -> Stat {
+        let cache = || self.func("completion_cache").index(Expr::name("name"));
+        let body = vec![
+            Stat::Assign(cache(), cache().binop(BinOp::Or, Expr::table([]))),
+            Stat::Assign(
+                cache().index(Expr::name("key")),
+                Expr::table([
+                    Field::named("lines", Expr::name("lines")),
+                    Field::named("time", Expr::name("os").field("time").call([])),
+                ]),
+            ),
+        ];
+        self.define_helper("cache_lines", &["name", "key", "lines"], body)
+    }
+
+    /// Tells the async completions of the arg are loading, instead of its hint.
+    fn add_loading_hint(&mut self, arg: &Position, mut hint: Field) -> Field {
+        if let Candidates::Run(id) = arg.candidates
+            && self.completor.is_some()
+            && self
+                .graph
+                .source(id)
+                .run_async
+                .unwrap_or(self.async_complete)
+        {
+            let loading_hint_function = self.loading_hint_function();
+            self.functions
+                .insert("loading_hint".into(), loading_hint_function);
+            let name = Expr::str(&self.graph.source(id).name);
+            hint.value = self.func("loading_hint").call([name, hint.value]);
+        }
+        hint
+    }
+
+    /// Reads the output lines of a command run by `io.popen` or `io.popenyield`.
+    fn read_lines_function(&self) -> Stat {
+        let body = vec![
+            Stat::Call(Expr::name("assert").call([
+                Expr::name("pipe"),
+                Expr::str("[ERROR]: failed to run complete command"),
+            ])),
+            Stat::local("lines", Expr::table([])),
+            Stat::ForIn(
+                vec!["line".into()],
+                Expr::name("pipe").method("lines", []),
+                vec![Stat::Call(
+                    Expr::name("table")
+                        .field("insert")
+                        .call([Expr::name("lines"), Expr::name("line")]),
+                )],
+            ),
+            Stat::Call(Expr::name("pipe").method("close", [])),
+            Stat::Return(Some(Expr::name("lines"))),
+        ];
+        self.define_helper("read_lines", &["pipe"], body)
+    }
+
+    /// The latest async completion request, the older ones being stale.
+    fn async_completion_table(&self) -> Stat {
+        self.define_store("async_completion")
+    }
+
+    /// Returns the output lines of the command run in the background for the completer and the
+    /// key, or nothing while they're loading. Runs it in the foreground if clink can't run it in
+    /// the background. The lines are given to `store` once loaded, if any.
+    ///
+    /// The request is kept while loading, so that typing doesn't run the command again, and
+    /// reused once loaded for the same line.
+    fn complete_async_function(&self) -> Stat {
+        let request = || Expr::name("request");
+        let latest = || self.func("async_completion");
+        let reclassify = || Stat::Call(Expr::name("clink").field("reclassifyline").call([]));
+        let store = |lines: Expr| {
+            Stat::If(
+                vec![(
+                    Expr::name("store"),
+                    vec![Stat::Call(Expr::name("store").call([
+                        Expr::name("name"),
+                        Expr::name("key"),
+                        lines,
+                    ]))],
+                )],
+                None,
+            )
+        };
+        let background = Expr::function(
+            &[] as &[&str],
+            vec![
+                Stat::Comment(
+                    "Cancelled if another one was requested before it started, else cached".into(),
+                ),
+                Stat::If(
+                    vec![(
+                        latest().binop(BinOp::Ne, request()),
+                        vec![Stat::Return(None)],
+                    )],
+                    None,
+                ),
+                Stat::Comment(
+                    "Shows the loading hint, then the hint of the arg once loaded".into(),
+                ),
+                reclassify(),
+                Stat::Assign(
+                    request().field("lines"),
+                    self.func("read_lines").call([Expr::name("io")
+                        .field("popenyield")
+                        .call([Expr::name("command")])]),
+                ),
+                store(request().field("lines")),
+                Stat::If(
+                    vec![(latest().binop(BinOp::Eq, request()), vec![reclassify()])],
+                    None,
+                ),
+            ],
+        );
+        let same_request = request()
+            .field("name")
+            .binop(BinOp::Eq, Expr::name("name"))
+            .binop(
+                BinOp::And,
+                request().field("key").binop(BinOp::Eq, Expr::name("key")),
+            )
+            .binop(
+                BinOp::And,
+                Expr::unop(UnOp::Not, request().field("lines"))
+                    .binop(
+                        BinOp::Or,
+                        request().field("line").binop(BinOp::Eq, Expr::name("line")),
+                    )
+                    .paren(),
+            );
+        let body = vec![
+            Stat::local("request", latest()),
+            Stat::If(
+                vec![(
+                    same_request,
+                    vec![Stat::Return(Some(request().field("lines")))],
+                )],
+                None,
+            ),
+            Stat::If(
+                vec![(
+                    Expr::unop(
+                        UnOp::Not,
+                        Expr::name("io")
+                            .field("popenyield")
+                            .binop(BinOp::And, Expr::name("clink").field("addcoroutine")),
+                    ),
+                    vec![
+                        Stat::Comment(
+                            "Older clink versions can only run it in the foreground".into(),
+                        ),
+                        Stat::local(
+                            "lines",
+                            self.func("read_lines").call([Expr::name("io")
+                                .field("popen")
+                                .call([Expr::name("command")])]),
+                        ),
+                        store(Expr::name("lines")),
+                        Stat::Return(Some(Expr::name("lines"))),
+                    ],
+                )],
+                None,
+            ),
+            Stat::Assign(
+                request(),
+                Expr::table([
+                    Field::named("name", Expr::name("name")),
+                    Field::named("key", Expr::name("key")),
+                    Field::named("line", Expr::name("line")),
+                ]),
+            ),
+            Stat::Assign(latest(), request()),
+            Stat::Call(
+                Expr::name("clink")
+                    .field("addcoroutine")
+                    .call([Expr::name("coroutine").field("create").call([background])]),
+            ),
+        ];
+        self.define_helper(
+            "complete_async",
+            &["name", "key", "line", "command", "store"],
+            body,
+        )
+    }
+
+    /// Replaces the hint of an arg while the async completions of its completer are loading.
+    fn loading_hint_function(&self) -> Stat {
+        let params = ["arg_index", "word", "word_index", "line_state", "user_data"];
+        let request = || Expr::name("request");
+        let hint = Expr::function(
+            &params,
+            vec![
+                Stat::local("request", self.func("async_completion")),
+                Stat::If(
+                    vec![(
+                        request()
+                            .field("name")
+                            .binop(BinOp::Eq, Expr::name("name"))
+                            .binop(BinOp::And, Expr::unop(UnOp::Not, request().field("lines"))),
+                        vec![Stat::Return(Some(Expr::str("Loading...")))],
+                    )],
+                    None,
+                ),
+                Stat::If(
+                    vec![(
+                        Expr::name("type")
+                            .call([Expr::name("hint")])
+                            .binop(BinOp::Eq, Expr::str("function")),
+                        vec![Stat::Return(Some(
+                            Expr::name("hint").call(params.map(Expr::name)),
+                        ))],
+                    )],
+                    None,
+                ),
+                Stat::Return(Some(Expr::name("hint"))),
+            ],
+        );
+        self.define_helper(
+            "loading_hint",
+            &["name", "hint"],
+            vec![Stat::Return(Some(hint))],
+        )
+    }
+
+    /// The loop body collecting a line of the completer output as a string.
+    fn add_plain_match(descs: bool) -> Block {
+        let mut body = vec![];
+        if descs {
+            body.push(Stat::Comment("for filtering out descriptions".into()));
+            body.push(Stat::Assign(
+                Expr::name("line"),
+                Expr::name("line").method("match", [Expr::str("^([^:]+):")]),
+            ));
+        }
+        body.push(Stat::Call(
+            Expr::name("table")
+                .field("insert")
+                .call([Expr::name("complete_args"), Expr::name("line")]),
+        ));
+        body
+    }
+
+    /// The loop body adding a line of the completer output to the match builder, along with its
+    /// type and description. The part before the description is displayed, instead of the line.
+    fn add_typed_match(&mut self, descs: bool, match_type: MatchType) -> Block {
+        let mut body = vec![];
+        let word = match descs {
+            true => {
+                body.push(Stat::Comment("for splitting out descriptions".into()));
+                body.push(Stat::local(
+                    "match",
+                    Expr::name("line")
+                        .method("match", [Expr::str("^([^:]+):")])
+                        .binop(BinOp::Or, Expr::name("line")),
+                ));
+                body.push(Stat::local(
+                    "description",
+                    Expr::name("line").method("match", [Expr::str("^[^:]+:(.*)$")]),
+                ));
+                Expr::name("match")
+            }
+            false => Expr::name("line"),
+        };
+        let type_ = match match_type {
+            MatchType::Word => Expr::str("word"),
+            MatchType::Arg => Expr::str("arg"),
+            MatchType::File => Expr::str("file"),
+            MatchType::Dir => Expr::str("dir"),
+            MatchType::Auto => {
+                let match_type_function = self.match_type_function();
+                self.functions
+                    .insert("match_type".into(), match_type_function);
+                self.func("match_type").call([word.clone()])
+            }
+        };
+        let mut fields = vec![
+            Field::named("match", word.clone()),
+            Field::named("display", word),
+        ];
+        if descs {
+            fields.push(Field::named("description", Expr::name("description")));
+        }
+        fields.push(Field::named("type", type_));
+        body.push(Stat::Call(
+            Expr::name("match_builder").method("addmatch", [Expr::table(fields)]),
+        ));
+        body
+    }
+
+    /// Detects the type of a match: a dir if it ends with a path separator or is an existing
+    /// dir, a file if it's an existing file, else a word.
+    fn match_type_function(&self) -> Stat {
+        let word = || Expr::name("match");
+        let body = vec![
+            Stat::If(
+                vec![
+                    (
+                        word()
+                            .method("find", [Expr::str("[/\\]$")])
+                            .binop(BinOp::Or, Expr::name("os").field("isdir").call([word()])),
+                        vec![Stat::Return(Some(Expr::str("dir")))],
+                    ),
+                    (
+                        Expr::name("os").field("isfile").call([word()]),
+                        vec![Stat::Return(Some(Expr::str("file")))],
+                    ),
+                ],
+                None,
+            ),
+            Stat::Return(Some(Expr::str("word"))),
+        ];
+        self.define_helper("match_type", &["match"], body)
+    }
+}
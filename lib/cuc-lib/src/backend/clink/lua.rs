@@ -0,0 +1,998 @@
+//! A small Lua AST, just enough for the argmatcher scripts, along with its printer.
+//!
+//! The generator builds the script out of these nodes, so the delimiters,
+//! parentheses and indentation are only ever handled by the [`Printer`].
+
+use std::fmt::{self, Write};
+
+pub type Block = Vec<Stat>;
+
+#[derive(Debug, Clone)]
+pub enum Stat {
+    /// `local name = value`
+    Local(String, Option<Expr>),
+    /// `target = value`
+    Assign(Expr, Expr),
+    /// A function or method call used as a statement.
+    Call(Expr),
+    Return(Option<Expr>),
+    /// `if cond then ... elseif cond then ... else ... end`
+    If(Vec<(Expr, Block)>, Option<Block>),
+    /// `for names in iter do ... end`
+    ForIn(Vec<String>, Expr, Block),
+    /// `[local] function name(params) ... end`, the name may be a field e.g. `mod.name`.
+    Function {
+        local: bool,
+        name: String,
+        func: Function,
+    },
+    /// Only printed in the pretty style.
+    Comment(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Nil,
+    Bool(bool),
+    Number(i128),
+    /// A quoted string, escaped by the printer.
+    Str(String),
+    /// A long bracket string, e.g. `[==[...]==]`, the level being picked by the printer.
+    LongStr(String),
+    Name(String),
+    /// `prefix.name`
+    Field(Box<Expr>, String),
+    /// `prefix[key]`
+    Index(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    /// `prefix:name(args)`
+    Method(Box<Expr>, String, Vec<Expr>),
+    Table(Vec<Field>),
+    Function(Function),
+    BinOp(Box<Expr>, BinOp, Box<Expr>),
+    UnOp(UnOp, Box<Expr>),
+    /// Explicit parentheses, only needed for readability as the printer adds the required ones.
+    Paren(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: Block,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    /// Positional if none.
+    pub key: Option<String>,
+    pub value: Expr,
+    /// Only printed in the pretty style, e.g. `--[[alias]]`.
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Or,
+    And,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Not,
+    Neg,
+    Len,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    #[default]
+    Pretty,
+    /// No indentation, newlines or comments.
+    Minified,
+}
+
+impl Expr {
+    pub fn name<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Name(name.into())
+    }
+
+    pub fn str<S>(value: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Str(value.into())
+    }
+
+    pub fn long_str<S>(value: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::LongStr(value.into())
+    }
+
+    pub fn table<I>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = Field>,
+    {
+        Self::Table(fields.into_iter().collect())
+    }
+
+    pub fn function<P>(params: &[P], body: Block) -> Self
+    where
+        P: AsRef<str>,
+    {
+        Self::Function(Function::new(params, body))
+    }
+
+    pub fn field<S>(self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Field(Box::new(self), name.into())
+    }
+
+    pub fn index(self, key: Expr) -> Self {
+        Self::Index(Box::new(self), Box::new(key))
+    }
+
+    pub fn call<I>(self, args: I) -> Self
+    where
+        I: IntoIterator<Item = Expr>,
+    {
+        Self::Call(Box::new(self), args.into_iter().collect())
+    }
+
+    pub fn method<S, I>(self, name: S, args: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = Expr>,
+    {
+        Self::Method(Box::new(self), name.into(), args.into_iter().collect())
+    }
+
+    pub fn binop(self, op: BinOp, rhs: Expr) -> Self {
+        Self::BinOp(Box::new(self), op, Box::new(rhs))
+    }
+
+    pub fn unop(op: UnOp, expr: Expr) -> Self {
+        Self::UnOp(op, Box::new(expr))
+    }
+
+    pub fn paren(self) -> Self {
+        Self::Paren(Box::new(self))
+    }
+
+    /// Concatenates all the exprs, nesting to the right as `..` is right associative.
+    pub fn concat<I>(exprs: I) -> Self
+    where
+        I: IntoIterator<Item = Expr>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let mut exprs = exprs.into_iter().rev();
+        let last = exprs.next().unwrap_or_else(|| Expr::str(""));
+        exprs.fold(last, |rhs, lhs| lhs.binop(BinOp::Concat, rhs))
+    }
+
+    /// Whether it can be called or indexed without being enclosed in parentheses.
+    fn is_prefix(&self) -> bool {
+        matches!(
+            self,
+            Self::Name(_)
+                | Self::Field(..)
+                | Self::Index(..)
+                | Self::Call(..)
+                | Self::Method(..)
+                | Self::Paren(_)
+        )
+    }
+
+    fn is_call(&self) -> bool {
+        matches!(self, Self::Call(..) | Self::Method(..))
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, Self::Table(fields) if !fields.is_empty())
+            || matches!(self, Self::Function(_))
+    }
+}
+
+impl Function {
+    pub fn new<P>(params: &[P], body: Block) -> Self
+    where
+        P: AsRef<str>,
+    {
+        Self {
+            params: params.iter().map(|p| p.as_ref().to_string()).collect(),
+            body,
+        }
+    }
+}
+
+impl Field {
+    pub fn positional(value: Expr) -> Self {
+        Self {
+            key: None,
+            value,
+            comment: None,
+        }
+    }
+
+    pub fn named<S>(key: S, value: Expr) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            key: Some(key.into()),
+            value,
+            comment: None,
+        }
+    }
+
+    pub fn with_comment<S>(mut self, comment: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.comment = Some(comment.into());
+        self
+    }
+}
+
+impl Stat {
+    pub fn local<S>(name: S, value: Expr) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Local(name.into(), Some(value))
+    }
+
+    pub fn function<S, P>(name: S, params: &[P], body: Block) -> Self
+    where
+        S: Into<String>,
+        P: AsRef<str>,
+    {
+        Self::Function {
+            local: false,
+            name: name.into(),
+            func: Function::new(params, body),
+        }
+    }
+
+    pub fn local_function<S, P>(name: S, params: &[P], body: Block) -> Self
+    where
+        S: Into<String>,
+        P: AsRef<str>,
+    {
+        Self::Function {
+            local: true,
+            name: name.into(),
+            func: Function::new(params, body),
+        }
+    }
+}
+
+impl BinOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Or => "or",
+            Self::And => "and",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Le => "<=",
+            Self::Ge => ">=",
+            Self::Eq => "==",
+            Self::Ne => "~=",
+            Self::Concat => "..",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Mod => "%",
+            Self::Pow => "^",
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Lt | Self::Gt | Self::Le | Self::Ge | Self::Eq | Self::Ne => 3,
+            Self::Concat => 4,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Mod => 6,
+            Self::Pow => 8,
+        }
+    }
+
+    fn is_right_assoc(&self) -> bool {
+        matches!(self, Self::Concat | Self::Pow)
+    }
+}
+
+impl UnOp {
+    const PRECEDENCE: u8 = 7;
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Not => "not ",
+            Self::Neg => "-",
+            Self::Len => "#",
+        }
+    }
+}
+
+/// Prints the AST to a [`fmt::Write`] sink, in a single pass over it.
+pub struct Printer<W> {
+    style: Style,
+    level: usize,
+    out: W,
+    /// Whether the last top-level statement is a function, none before the first one.
+    last: Option<bool>,
+}
+
+impl Printer<String> {
+    pub fn print(style: Style, chunk: &[Stat]) -> String {
+        let mut out = String::new();
+        Printer::write(style, chunk, &mut out).expect("writing to a String can't fail");
+        out
+    }
+}
+
+impl<W: Write> Printer<W> {
+    const INDENT: &'static str = "\t";
+    /// Tables longer than this are split into a field per line, in the pretty style.
+    const MAX_INLINE_TABLE: usize = 100;
+
+    pub fn new(style: Style, out: W) -> Self {
+        Self {
+            style,
+            level: 0,
+            out,
+            last: None,
+        }
+    }
+
+    /// Prints the chunk to the sink as it goes, without buffering the output.
+    pub fn write(style: Style, chunk: &[Stat], out: W) -> fmt::Result {
+        let mut printer = Printer::new(style, out);
+        for stat in chunk {
+            printer.push(stat)?;
+        }
+        printer.finish()
+    }
+
+    /// Prints a top-level statement of the chunk, e.g. as soon as it's generated.
+    pub fn push(&mut self, stat: &Stat) -> fmt::Result {
+        if !Self::is_printed(self.style, stat) {
+            return Ok(());
+        }
+        let is_function = matches!(stat, Stat::Function { .. });
+        if let Some(last_is_function) = self.last {
+            // Top-level functions are separated by an empty line
+            if self.is_pretty() && (last_is_function || is_function) {
+                self.out.write_char('\n')?;
+            }
+            self.newline()?;
+        }
+        self.last = Some(is_function);
+        self.stat(stat)
+    }
+
+    /// Ends the chunk, once all of its statements are pushed.
+    pub fn finish(mut self) -> fmt::Result {
+        if self.is_pretty() {
+            self.out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    fn is_pretty(&self) -> bool {
+        self.style == Style::Pretty
+    }
+
+    fn newline(&mut self) -> fmt::Result {
+        if self.is_pretty() {
+            self.out.write_char('\n')?;
+            for _ in 0..self.level {
+                self.out.write_str(Self::INDENT)?;
+            }
+            Ok(())
+        } else {
+            self.out.write_char(' ')
+        }
+    }
+
+    fn is_printed(style: Style, stat: &Stat) -> bool {
+        style == Style::Pretty || !matches!(stat, Stat::Comment(_))
+    }
+
+    /// Prints the statements of a nested block, each on a new line except the first one.
+    fn block(&mut self, block: &[Stat]) -> fmt::Result {
+        let style = self.style;
+        for (i, stat) in block
+            .iter()
+            .filter(|s| Self::is_printed(style, s))
+            .enumerate()
+        {
+            if i > 0 {
+                self.newline()?;
+            }
+            self.stat(stat)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the statements of a nested block, followed by the closing keyword.
+    fn nested_block(&mut self, block: &[Stat], close: &str) -> fmt::Result {
+        self.level += 1;
+        if block.iter().any(|s| Self::is_printed(self.style, s)) {
+            self.newline()?;
+            self.block(block)?;
+        }
+        self.level -= 1;
+        self.newline()?;
+        self.out.write_str(close)
+    }
+
+    fn stat(&mut self, stat: &Stat) -> fmt::Result {
+        match stat {
+            Stat::Local(name, value) => {
+                self.out.write_str("local ")?;
+                self.out.write_str(name)?;
+                if let Some(value) = value {
+                    self.out.write_str(self.sep("="))?;
+                    self.expr(value)?;
+                }
+            }
+            Stat::Assign(target, value) => {
+                self.expr(target)?;
+                self.out.write_str(self.sep("="))?;
+                self.expr(value)?;
+            }
+            Stat::Call(call) => self.expr(call)?,
+            Stat::Return(value) => {
+                self.out.write_str("return")?;
+                if let Some(value) = value {
+                    self.out.write_char(' ')?;
+                    self.expr(value)?;
+                }
+            }
+            Stat::If(branches, otherwise) => {
+                for (index, (cond, block)) in branches.iter().enumerate() {
+                    self.out
+                        .write_str(if index == 0 { "if " } else { "elseif " })?;
+                    self.expr(cond)?;
+                    self.out.write_str(" then")?;
+                    self.level += 1;
+                    if block.iter().any(|s| Self::is_printed(self.style, s)) {
+                        self.newline()?;
+                        self.block(block)?;
+                    }
+                    self.level -= 1;
+                    self.newline()?;
+                }
+                if let Some(block) = otherwise {
+                    self.out.write_str("else")?;
+                    self.nested_block(block, "end")?;
+                } else {
+                    self.out.write_str("end")?;
+                }
+            }
+            Stat::ForIn(names, iter, block) => {
+                self.out.write_str("for ")?;
+                self.names(names)?;
+                self.out.write_str(" in ")?;
+                self.expr(iter)?;
+                self.out.write_str(" do")?;
+                self.nested_block(block, "end")?;
+            }
+            Stat::Function { local, name, func } => {
+                if *local {
+                    self.out.write_str("local ")?;
+                }
+                self.out.write_str("function ")?;
+                self.out.write_str(name)?;
+                self.function(func)?;
+            }
+            Stat::Comment(comment) => {
+                if comment.contains(['\n', '\r']) {
+                    let (open, close) = long_brackets(comment);
+                    write!(self.out, "--{}\n{}\n--{}", open, comment, close)?;
+                } else {
+                    write!(self.out, "-- {}", comment)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, func: &Function) -> fmt::Result {
+        self.out.write_char('(')?;
+        self.names(&func.params)?;
+        self.out.write_char(')')?;
+        self.nested_block(&func.body, "end")
+    }
+
+    fn names(&mut self, names: &[String]) -> fmt::Result {
+        for (index, name) in names.iter().enumerate() {
+            if index != 0 {
+                self.out.write_str(self.sep(","))?;
+            }
+            self.out.write_str(name)?;
+        }
+        Ok(())
+    }
+
+    /// A separator, with spaces around (or after for ',') in the pretty style.
+    fn sep(&self, sep: &'static str) -> &'static str {
+        match (self.style, sep) {
+            (Style::Pretty, "=") => " = ",
+            (Style::Pretty, ",") => ", ",
+            (_, sep) => sep,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> fmt::Result {
+        match expr {
+            Expr::Nil => self.out.write_str("nil"),
+            Expr::Bool(value) => self.out.write_str(if *value { "true" } else { "false" }),
+            Expr::Number(value) => write!(self.out, "{}", value),
+            Expr::Str(value) => write_quoted(&mut self.out, value),
+            Expr::LongStr(value) => write_long_str(&mut self.out, value),
+            Expr::Name(name) => self.out.write_str(name),
+            Expr::Field(prefix, name) => {
+                self.prefix(prefix)?;
+                self.out.write_char('.')?;
+                self.out.write_str(name)
+            }
+            Expr::Index(prefix, key) => {
+                self.prefix(prefix)?;
+                self.out.write_char('[')?;
+                self.expr(key)?;
+                self.out.write_char(']')
+            }
+            Expr::Call(prefix, args) => {
+                self.prefix(prefix)?;
+                self.args(args)
+            }
+            Expr::Method(prefix, name, args) => {
+                self.prefix(prefix)?;
+                // Chained calls are split into a line per method, e.g. `clink.argmatcher():nofiles()`
+                let chained = prefix.is_call() && self.is_pretty();
+                if chained {
+                    self.level += 1;
+                    self.newline()?;
+                }
+                self.out.write_char(':')?;
+                self.out.write_str(name)?;
+                self.args(args)?;
+                if chained {
+                    self.level -= 1;
+                }
+                Ok(())
+            }
+            Expr::Table(fields) => self.table(fields),
+            Expr::Function(func) => {
+                self.out.write_str("function")?;
+                self.function(func)
+            }
+            Expr::BinOp(lhs, op, rhs) => {
+                let precedence = op.precedence();
+                let (lhs_min, rhs_min) = if op.is_right_assoc() {
+                    (precedence + 1, precedence)
+                } else {
+                    (precedence, precedence + 1)
+                };
+                self.operand(lhs, lhs_min)?;
+                self.out.write_char(' ')?;
+                self.out.write_str(op.as_str())?;
+                self.out.write_char(' ')?;
+                self.operand(rhs, rhs_min)
+            }
+            Expr::UnOp(op, operand) => {
+                self.out.write_str(op.as_str())?;
+                // Avoiding "--" which would start a comment
+                let negative = matches!(**operand, Expr::UnOp(UnOp::Neg, _) | Expr::Number(..0));
+                if *op == UnOp::Neg && negative {
+                    self.operand(operand, u8::MAX)
+                } else {
+                    self.operand(operand, UnOp::PRECEDENCE)
+                }
+            }
+            Expr::Paren(expr) => self.parenthesized(expr),
+        }
+    }
+
+    fn parenthesized(&mut self, expr: &Expr) -> fmt::Result {
+        self.out.write_char('(')?;
+        self.expr(expr)?;
+        self.out.write_char(')')
+    }
+
+    /// Prints the expr, enclosed in parentheses if it binds looser than required.
+    fn operand(&mut self, expr: &Expr, min_precedence: u8) -> fmt::Result {
+        let precedence = match expr {
+            Expr::BinOp(_, op, _) => op.precedence(),
+            Expr::UnOp(..) | Expr::Number(..0) => UnOp::PRECEDENCE,
+            _ => u8::MAX - 1,
+        };
+        if precedence < min_precedence {
+            self.parenthesized(expr)
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    fn prefix(&mut self, expr: &Expr) -> fmt::Result {
+        if expr.is_prefix() {
+            self.expr(expr)
+        } else {
+            self.parenthesized(expr)
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) -> fmt::Result {
+        self.out.write_char('(')?;
+        for (index, arg) in args.iter().enumerate() {
+            if index != 0 {
+                self.out.write_str(self.sep(","))?;
+            }
+            self.expr(arg)?;
+        }
+        self.out.write_char(')')
+    }
+
+    fn table(&mut self, fields: &[Field]) -> fmt::Result {
+        if fields.is_empty() {
+            return self.out.write_str("{}");
+        }
+
+        let has_comment = fields.iter().any(|f| f.comment.is_some());
+        let has_nested = fields.len() > 1 && fields.iter().any(|f| f.value.is_nested());
+        let multiline =
+            self.is_pretty() && (has_comment || has_nested || !Self::fits_inline(fields));
+        if !multiline {
+            self.out
+                .write_str(if self.is_pretty() { "{ " } else { "{" })?;
+            for (index, field) in fields.iter().enumerate() {
+                if index != 0 {
+                    self.out.write_str(self.sep(","))?;
+                }
+                self.field(field)?;
+            }
+            return self
+                .out
+                .write_str(if self.is_pretty() { " }" } else { "}" });
+        }
+
+        self.out.write_char('{')?;
+        self.level += 1;
+        for (index, field) in fields.iter().enumerate() {
+            if index != 0 {
+                self.out.write_char(',')?;
+            }
+            self.newline()?;
+            if let Some(ref comment) = field.comment {
+                let (open, close) = long_brackets(comment);
+                write!(self.out, "--{}{}{} ", open, comment, close)?;
+            }
+            self.field(field)?;
+        }
+        self.level -= 1;
+        self.newline()?;
+        self.out.write_char('}')
+    }
+
+    fn field(&mut self, field: &Field) -> fmt::Result {
+        if let Some(ref key) = field.key {
+            if is_name(key) {
+                self.out.write_str(key)?;
+            } else {
+                self.out.write_char('[')?;
+                write_quoted(&mut self.out, key)?;
+                self.out.write_char(']')?;
+            }
+            self.out.write_str(self.sep("="))?;
+        }
+        self.expr(&field.value)
+    }
+
+    /// Whether the minified table fits in [`Self::MAX_INLINE_TABLE`]. Printing it stops as soon
+    /// as it doesn't, so that measuring nested tables stays linear.
+    fn fits_inline(fields: &[Field]) -> bool {
+        let mut len = InlineLen {
+            len: 0,
+            max: Self::MAX_INLINE_TABLE,
+        };
+        Printer::new(Style::Minified, &mut len)
+            .table(fields)
+            .is_ok()
+    }
+}
+
+/// Counts the printed length, failing once it exceeds the max.
+struct InlineLen {
+    len: usize,
+    max: usize,
+}
+
+impl Write for InlineLen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        if self.len > self.max {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Whether it's a valid Lua name, i.e. usable as an identifier.
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+/// Quotes the string as a Lua literal, escaping anything that isn't printable ASCII or UTF-8.
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    write_quoted(&mut quoted, value).expect("writing to a String can't fail");
+    quoted
+}
+
+/// Writes the string quoted, see [`quote`].
+fn write_quoted<W: Write>(out: &mut W, value: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            // Always 3 digits, so a following digit isn't taken as part of the escape
+            c if c.is_ascii_control() => write!(out, "\\{:03}", c as u8)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Returns the lowest level long brackets, e.g. `[==[` and `]==]`, which can enclose the text.
+///
+/// The closing bracket must neither occur in the text, nor be completed by its end.
+pub fn long_brackets(text: &str) -> (String, String) {
+    let level = "=".repeat(long_bracket_level(text));
+    (format!("[{}[", level), format!("]{}]", level))
+}
+
+/// The level of [`long_brackets`], i.e. the count of `=`.
+fn long_bracket_level(text: &str) -> usize {
+    if !text.contains(']') {
+        return 0;
+    }
+    let mut close = String::from("]]");
+    loop {
+        let unclosed = &close[..close.len() - 1];
+        if !text.contains(close.as_str()) && !text.ends_with(unclosed) {
+            return close.len() - 2;
+        }
+        close.insert(1, '=');
+    }
+}
+
+/// Encloses the string in long brackets, falling back to a quoted string if that can't be exact.
+pub fn long_str(value: &str) -> String {
+    let mut enclosed = String::with_capacity(value.len() + 4);
+    write_long_str(&mut enclosed, value).expect("writing to a String can't fail");
+    enclosed
+}
+
+/// Writes the string enclosed in long brackets, see [`long_str`].
+fn write_long_str<W: Write>(out: &mut W, value: &str) -> fmt::Result {
+    // Lua converts any end of line sequence to '\n' in long strings
+    if value.contains('\r') {
+        return write_quoted(out, value);
+    }
+    let level = long_bracket_level(value);
+    out.write_char('[')?;
+    for _ in 0..level {
+        out.write_char('=')?;
+    }
+    out.write_char('[')?;
+    // A newline right after the opening bracket is skipped, so it's doubled to be kept
+    if value.starts_with('\n') {
+        out.write_char('\n')?;
+    }
+    out.write_str(value)?;
+    out.write_char(']')?;
+    for _ in 0..level {
+        out.write_char('=')?;
+    }
+    out.write_char(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Evaluates a printed string literal with Lua itself, returning its bytes.
+    fn eval_str(printed: &str) -> mlua::Result<Vec<u8>> {
+        let lua = mlua::Lua::new();
+        let value = lua
+            .load(format!("return {}", printed))
+            .eval::<mlua::String>()?;
+        Ok(value.as_bytes().to_vec())
+    }
+
+    fn pretty(chunk: &[Stat]) -> String {
+        Printer::print(Style::Pretty, chunk)
+    }
+
+    fn minified(chunk: &[Stat]) -> String {
+        Printer::print(Style::Minified, chunk)
+    }
+
+    #[test]
+    fn test_is_name() {
+        assert!(is_name("_cmd_foo"));
+        assert!(is_name("a1"));
+        assert!(!is_name("1a"));
+        assert!(!is_name("foo-bar"));
+        assert!(!is_name("end"));
+        assert!(!is_name(""));
+        assert!(!is_name("café"));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let expr = Expr::name("a")
+            .binop(BinOp::Add, Expr::name("b"))
+            .binop(BinOp::Mul, Expr::name("c"));
+        assert_eq!(minified(&[Stat::Return(Some(expr))]), "return (a + b) * c");
+
+        let expr = Expr::name("a").binop(
+            BinOp::Sub,
+            Expr::name("b").binop(BinOp::Sub, Expr::name("c")),
+        );
+        assert_eq!(minified(&[Stat::Return(Some(expr))]), "return a - (b - c)");
+
+        let expr = Expr::concat([Expr::name("a"), Expr::Number(1), Expr::name("c")]);
+        assert_eq!(minified(&[Stat::Return(Some(expr))]), "return a .. 1 .. c");
+
+        let expr = Expr::unop(UnOp::Neg, Expr::unop(UnOp::Neg, Expr::Number(1)));
+        assert_eq!(minified(&[Stat::Return(Some(expr))]), "return -(-1)");
+    }
+
+    #[test]
+    fn test_method_chain() {
+        let expr = Expr::name("clink")
+            .field("argmatcher")
+            .call([Expr::str("foo")])
+            .method(
+                "_addexarg",
+                [Expr::table([Field::positional(Expr::str("a"))])],
+            )
+            .method("nofiles", []);
+        assert_eq!(
+            pretty(&[Stat::Call(expr.clone())]),
+            "clink.argmatcher(\"foo\")\n\t:_addexarg({ \"a\" })\n\t:nofiles()\n"
+        );
+        assert_eq!(
+            minified(&[Stat::Call(expr)]),
+            "clink.argmatcher(\"foo\"):_addexarg({\"a\"}):nofiles()"
+        );
+    }
+
+    #[test]
+    fn test_multiline_table() {
+        let table = Expr::table([
+            Field::positional(Expr::table([Field::positional(Expr::str("-a"))])),
+            Field::positional(Expr::table([Field::positional(Expr::str("-b"))]))
+                .with_comment("alias"),
+            Field::named("hint", Expr::long_str("x")),
+        ]);
+        assert_eq!(
+            pretty(&[Stat::local("t", table.clone())]),
+            "local t = {\n\t{ \"-a\" },\n\t--[[alias]] { \"-b\" },\n\thint = [[x]]\n}\n"
+        );
+        assert_eq!(
+            minified(&[Stat::local("t", table)]),
+            "local t={{\"-a\"},{\"-b\"},hint=[[x]]}"
+        );
+    }
+
+    #[test]
+    fn test_function_and_if() {
+        let body = vec![
+            Stat::If(
+                vec![(
+                    Expr::unop(UnOp::Not, Expr::name("x")),
+                    vec![Stat::Return(Some(Expr::Number(1)))],
+                )],
+                Some(vec![Stat::Comment("otherwise".into())]),
+            ),
+            Stat::Return(Some(Expr::Number(0))),
+        ];
+        let chunk = [Stat::function("f", &["x"], body)];
+        assert_eq!(
+            pretty(&chunk),
+            "function f(x)\n\tif not x then\n\t\treturn 1\n\telse\n\t\t-- otherwise\n\tend\n\treturn 0\nend\n"
+        );
+        assert_eq!(
+            minified(&chunk),
+            "function f(x) if not x then return 1 else end return 0 end"
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(quote("a\"b\\c\nd"), r#""a\"b\\c\nd""#);
+        assert_eq!(quote("\u{0}1"), r#""\0001""#);
+        assert_eq!(quote("café"), "\"café\"");
+        assert_eq!(long_str("x"), "[[x]]");
+        assert_eq!(long_str("]]"), "[=[]]]=]");
+        assert_eq!(long_str("a]"), "[=[a]]=]");
+        assert_eq!(long_str("]===]]"), "[=[]===]]]=]");
+        assert_eq!(long_str("\nx"), "[[\n\nx]]");
+        assert_eq!(long_str("a\rb"), r#""a\rb""#);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_quoted_round_trips(value in any::<String>()) {
+            let printed = quote(&value);
+            prop_assert_eq!(eval_str(&printed).unwrap(), value.as_bytes());
+        }
+
+        #[test]
+        fn prop_long_str_round_trips(value in any::<String>()) {
+            let printed = long_str(&value);
+            prop_assert_eq!(eval_str(&printed).unwrap(), value.as_bytes());
+        }
+
+        #[test]
+        fn prop_long_str_round_trips_brackets(value in "[\\[\\]=\na]*") {
+            let printed = long_str(&value);
+            prop_assert_eq!(eval_str(&printed).unwrap(), value.as_bytes());
+        }
+
+        #[test]
+        fn prop_comment_ends_where_expected(comment in "(\\PC|[\\]=\n\r])*") {
+            let lua = mlua::Lua::new();
+            let chunk = [
+                Stat::Comment(comment.clone()),
+                Stat::Return(Some(Expr::str("end"))),
+            ];
+            let value: String = lua.load(pretty(&chunk)).eval().unwrap();
+            prop_assert_eq!(value, "end");
+
+            let table = Expr::table([Field::positional(Expr::str("x")).with_comment(comment)]);
+            let printed = pretty(&[Stat::Return(Some(table))]);
+            let value: Vec<String> = lua.load(printed).eval().unwrap();
+            prop_assert_eq!(value, ["x"]);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fmt, path::PathBuf};
+
+use super::{CompletionBackend, GenerateError, GenerateOptions, OutputFile, check_name_collisions};
+use crate::{graph::CompletionGraph, namespace};
+
+mod generator;
+pub mod lua;
+use generator::GeneratorView;
+use lua::{Printer, Style};
+
+/// The vendored `arghelper.lua`, which the generated scripts are built on.
+pub const ARGHELPER: &str = include_str!("../../../assets/arghelper.lua");
+/// Version of cuc-lib bundling [`ARGHELPER`], as recorded by `cuc modules install`.
+pub const ARGHELPER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Generates a clink argmatcher lua script, built on top of `arghelper.lua`.
+pub struct ClinkBackend;
+
+impl CompletionBackend for ClinkBackend {
+    fn name(&self) -> &'static str {
+        "clink"
+    }
+
+    fn about(&self) -> &'static str {
+        "Clink argmatcher lua script"
+    }
+
+    fn generate(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+    ) -> Result<String, GenerateError> {
+        let mut out = String::new();
+        self.write(graph, options, &mut out)?;
+        Ok(out)
+    }
+
+    fn write(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+        out: &mut dyn fmt::Write,
+    ) -> Result<(), GenerateError> {
+        let mut genv = generator_view(graph, options, None);
+        check_name_collisions(genv.func_names())?;
+        genv.write(style(options), out)?;
+        Ok(())
+    }
+
+    /// Generates `BIN.lua` along with `BIN/CMD.lua` per top-level cmd, where `BIN` and `CMD`
+    /// are mangled. The root script loads the file of a cmd when clink first reaches it.
+    fn generate_split(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+    ) -> Result<Vec<OutputFile>, GenerateError> {
+        let split = namespace::mangle(&[&graph.bin]);
+        let mut genv = generator_view(graph, options, Some(split.clone()));
+        check_name_collisions(genv.func_names())?;
+
+        let chunk = genv.generate();
+        let mut files = vec![OutputFile {
+            path: PathBuf::from(format!("{}.lua", split)),
+            content: Printer::print(style(options), &chunk),
+        }];
+        for &cmd_id in graph.matcher(graph.root()).subcmds.iter() {
+            if graph.matcher(cmd_id).is_empty() {
+                continue;
+            }
+            let chunk = genv.generate_cmd_file(cmd_id);
+            files.push(OutputFile {
+                path: PathBuf::from(&split).join(genv.cmd_file_name(cmd_id)),
+                content: Printer::print(style(options), &chunk),
+            });
+        }
+        Ok(files)
+    }
+}
+
+fn generator_view<'me>(
+    graph: &'me CompletionGraph,
+    options: &'me GenerateOptions,
+    split: Option<String>,
+) -> GeneratorView<'me> {
+    GeneratorView {
+        graph,
+        functions: Default::default(),
+        completor: options.completor.as_ref(),
+        arg_matchers: &options.arg_matchers,
+        standalone: options.standalone,
+        opteq: options.opteq,
+        suggest_defaults: options.suggest_defaults,
+        preserve_order: options.preserve_order,
+        async_complete: options.async_complete,
+        cache: options.cache,
+        // The files of the split can't see the locals of the root script, so they share its module
+        module: (options.module || split.is_some())
+            .then(|| format!("cuc_{}", namespace::mangle(&[&graph.bin]))),
+        split,
+    }
+}
+
+fn style(options: &GenerateOptions) -> Style {
+    match options.minify {
+        true => Style::Minified,
+        false => Style::Pretty,
+    }
+}
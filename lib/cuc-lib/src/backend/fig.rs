@@ -0,0 +1,513 @@
+use std::fmt::{self, Write};
+
+use super::{CompletionBackend, Completor, GenerateError, GenerateOptions, check_name_collisions};
+use crate::{
+    graph::{Candidates, CompletionGraph, FlagId, MatcherId, Position, SourceId, Word},
+    mbase64, namespace,
+    usage::{CachePolicy, MatchType},
+};
+
+/// Generates a Fig `CompletionSpec` (as consumed by inshellisense and Amazon Q)
+/// written in TypeScript.
+pub struct FigBackend;
+
+impl CompletionBackend for FigBackend {
+    fn name(&self) -> &'static str {
+        "fig"
+    }
+
+    fn about(&self) -> &'static str {
+        "Fig completion spec in typescript, as used by inshellisense"
+    }
+
+    fn generate(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+    ) -> Result<String, GenerateError> {
+        let mut out = String::new();
+        self.write(graph, options, &mut out)?;
+        Ok(out)
+    }
+
+    fn write(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+        out: &mut dyn Write,
+    ) -> Result<(), GenerateError> {
+        check_supported(options)?;
+        check_name_collisions(graph.sources.iter().map(|source| {
+            let func_name = namespace::arg_complete_func_name(&source.name);
+            (func_name, format!("complete '{}'", source.name))
+        }))?;
+        let genv = FigGeneratorView {
+            graph,
+            completor: options.completor.as_ref(),
+            arg_matchers: &options.arg_matchers,
+        };
+        genv.write(out)?;
+        Ok(())
+    }
+}
+
+/// Fails on the options that fig would ignore. The hidden items are left to the graph, which
+/// keeps the ones of `--hidden hide` as fig's `hidden` suggestions.
+fn check_supported(options: &GenerateOptions) -> Result<(), GenerateError> {
+    let unsupported = [
+        (options.minify, "minified output"),
+        (options.module, "module output"),
+        (options.standalone, "embedding the helper modules"),
+        (options.opteq, "completing flag args after '='"),
+        (options.suggest_defaults, "suggesting defaults"),
+        (options.preserve_order, "preserving the order"),
+        (options.async_complete, "async completions"),
+        (
+            options.cache != CachePolicy::Off,
+            "caching completions in the shell",
+        ),
+    ];
+    match unsupported.into_iter().find(|(set, _)| *set) {
+        Some((_, feature)) => Err(GenerateError::Unsupported {
+            backend: "fig",
+            feature,
+        }),
+        None => Ok(()),
+    }
+}
+
+struct FigGeneratorView<'me> {
+    graph: &'me CompletionGraph,
+    completor: Option<&'me Completor>,
+    arg_matchers: &'me [String],
+}
+
+impl FigGeneratorView<'_> {
+    const INDENT: &'static str = "  ";
+
+    /// The tokens are quoted for the splitter of `cuc complete` before being joined,
+    /// then hex encoded as the clink scripts do, so that a token can't split apart.
+    const LINE_HEX: &'static str = r#"const _line_hex = (tokens: string[]): string =>
+  Array.from(
+    new TextEncoder().encode(
+      tokens
+        .map((token) => token.replace(/(\\*)"/g, '$1$1\\"').replace(/(\\+)$/, "$1$1"))
+        .map((token) => `"${token}"`)
+        .join(" "),
+    ),
+    (byte) => byte.toString(16).padStart(2, "0"),
+  ).join("");"#;
+
+    /// Writes the spec to the sink as it goes. The generators are hoisted to top-level
+    /// constants, so that args sharing the same `complete` node reuse a single definition,
+    /// which takes a first walk over the args to write them before the spec.
+    fn write(&self, out: &mut dyn Write) -> fmt::Result {
+        let mut sources = vec![];
+        if self.completor.is_some() {
+            self.collect_sources(self.graph.root(), &mut sources);
+        }
+        if !sources.is_empty() {
+            writeln!(out, "{}\n", Self::LINE_HEX)?;
+        }
+        for id in sources {
+            self.write_generator(out, id)?;
+        }
+
+        let names: Vec<&String> = if self.arg_matchers.is_empty() {
+            vec![&self.graph.bin]
+        } else {
+            self.arg_matchers.iter().collect()
+        };
+        let level = 1;
+        writeln!(out, "const completionSpec: Fig.Spec = {{")?;
+        Self::add_name(out, &names, level)?;
+        self.add_matcher_body(out, self.graph.root(), level)?;
+        writeln!(out, "}};\n")?;
+        writeln!(out, "export default completionSpec;")
+    }
+
+    /// The sources of the dynamic args, once each in the order the args are written.
+    fn collect_sources(&self, id: MatcherId, sources: &mut Vec<SourceId>) {
+        let matcher = self.graph.matcher(id);
+        let global_flags = matcher
+            .global_flags
+            .map(|table_id| self.graph.flag_table(table_id).flags.as_slice())
+            .unwrap_or_default();
+        let flag_args = matcher
+            .flags
+            .iter()
+            .chain(global_flags)
+            .filter_map(|flag_id| self.graph.flag(*flag_id).arg.as_ref());
+        for arg in flag_args {
+            Self::collect_source(arg, sources);
+        }
+        for cmd_id in matcher.subcmds.iter() {
+            self.collect_sources(*cmd_id, sources);
+        }
+        for arg in matcher.positions.iter() {
+            Self::collect_source(arg, sources);
+        }
+    }
+
+    fn collect_source(arg: &Position, sources: &mut Vec<SourceId>) {
+        if let Candidates::Run(source_id) = arg.candidates
+            && !arg.hidden
+            && !sources.contains(&source_id)
+        {
+            sources.push(source_id);
+        }
+    }
+
+    fn indent(out: &mut dyn Write, level: usize) -> fmt::Result {
+        for _ in 0..level {
+            out.write_str(Self::INDENT)?;
+        }
+        Ok(())
+    }
+
+    fn add_property(out: &mut dyn Write, key: &str, value: &str, level: usize) -> fmt::Result {
+        Self::indent(out, level)?;
+        writeln!(out, "{}: {},", key, value)
+    }
+
+    /// Fig accepts either a single name or an array of names, the first one being
+    /// the one displayed.
+    fn add_name<S>(out: &mut dyn Write, names: &[S], level: usize) -> fmt::Result
+    where
+        S: AsRef<str>,
+    {
+        let value = match names {
+            [name] => js_string(name.as_ref()),
+            names => {
+                let names: Vec<String> = names.iter().map(|n| js_string(n.as_ref())).collect();
+                format!("[{}]", names.join(", "))
+            }
+        };
+        Self::add_property(out, "name", &value, level)
+    }
+
+    fn add_description(out: &mut dyn Write, help: &str, level: usize) -> fmt::Result {
+        if !help.is_empty() {
+            Self::add_property(out, "description", &js_string(help), level)?;
+        }
+        Ok(())
+    }
+
+    /// Fig can only hide a suggestion as a whole, i.e. along with all of its names.
+    fn add_hidden(out: &mut dyn Write, words: &[Word], level: usize) -> fmt::Result {
+        if words.first().is_some_and(|w| w.hidden) {
+            Self::add_property(out, "hidden", "true", level)?;
+        }
+        Ok(())
+    }
+
+    fn add_matcher_body(&self, out: &mut dyn Write, id: MatcherId, level: usize) -> fmt::Result {
+        let matcher = self.graph.matcher(id);
+        // Global flags are only added where declared, fig inherits the persistent ones.
+        let global_flags = matcher
+            .global_flags
+            .map(|table_id| self.graph.flag_table(table_id).flags.as_slice())
+            .unwrap_or_default();
+        self.add_options(out, &matcher.flags, global_flags, level)?;
+        self.add_subcommands(out, &matcher.subcmds, level)?;
+        self.add_args(out, &matcher.positions, level)
+    }
+
+    fn add_subcommands(
+        &self,
+        out: &mut dyn Write,
+        cmds: &[MatcherId],
+        level: usize,
+    ) -> fmt::Result {
+        if cmds.is_empty() {
+            return Ok(());
+        }
+
+        Self::indent(out, level)?;
+        out.write_str("subcommands: [\n")?;
+        for cmd_id in cmds.iter() {
+            let matcher = self.graph.matcher(*cmd_id);
+            Self::indent(out, level + 1)?;
+            out.write_str("{\n")?;
+
+            let names: Vec<&String> = matcher.words.iter().map(|w| &w.name).collect();
+            Self::add_name(out, &names, level + 2)?;
+            Self::add_description(out, &matcher.help, level + 2)?;
+            Self::add_hidden(out, &matcher.words, level + 2)?;
+            self.add_matcher_body(out, *cmd_id, level + 2)?;
+
+            Self::indent(out, level + 1)?;
+            out.write_str("},\n")?;
+        }
+        Self::indent(out, level)?;
+        out.write_str("],\n")
+    }
+
+    fn add_options(
+        &self,
+        out: &mut dyn Write,
+        flags: &[FlagId],
+        global_flags: &[FlagId],
+        level: usize,
+    ) -> fmt::Result {
+        if flags.is_empty() && global_flags.is_empty() {
+            return Ok(());
+        }
+
+        Self::indent(out, level)?;
+        out.write_str("options: [\n")?;
+        let flags = flags.iter().map(|id| (id, false));
+        let global_flags = global_flags.iter().map(|id| (id, true));
+        for (flag_id, is_global) in flags.chain(global_flags) {
+            let flag = self.graph.flag(*flag_id);
+            Self::indent(out, level + 1)?;
+            out.write_str("{\n")?;
+
+            let names: Vec<&String> = flag.words.iter().map(|w| &w.name).collect();
+            Self::add_name(out, &names, level + 2)?;
+            Self::add_description(out, &flag.help, level + 2)?;
+            Self::add_hidden(out, &flag.words, level + 2)?;
+            if is_global {
+                Self::add_property(out, "isPersistent", "true", level + 2)?;
+            }
+            if flag.repeatable {
+                Self::add_property(out, "isRepeatable", "true", level + 2)?;
+            }
+            if flag.required {
+                Self::add_property(out, "isRequired", "true", level + 2)?;
+            }
+
+            if let Some(ref arg) = flag.arg {
+                Self::indent(out, level + 2)?;
+                out.write_str("args: ")?;
+                self.add_arg(out, arg, level + 2)?;
+            }
+
+            Self::indent(out, level + 1)?;
+            out.write_str("},\n")?;
+        }
+        Self::indent(out, level)?;
+        out.write_str("],\n")
+    }
+
+    fn add_args(&self, out: &mut dyn Write, args: &[Position], level: usize) -> fmt::Result {
+        if args.is_empty() {
+            return Ok(());
+        }
+
+        Self::indent(out, level)?;
+        out.write_str("args: [\n")?;
+        for arg in args {
+            Self::indent(out, level + 1)?;
+            self.add_arg(out, arg, level + 1)?;
+        }
+        Self::indent(out, level)?;
+        out.write_str("],\n")
+    }
+
+    /// Expects the caller to have indented the line, as the arg may be the value of a property.
+    fn add_arg(&self, out: &mut dyn Write, arg: &Position, level: usize) -> fmt::Result {
+        out.write_str("{\n")?;
+
+        let name = if arg.name.is_empty() {
+            arg_display_name(&arg.repr)
+        } else {
+            arg_display_name(&arg.name)
+        };
+        Self::add_property(out, "name", &js_string(name), level + 1)?;
+        if !arg.required {
+            Self::add_property(out, "isOptional", "true", level + 1)?;
+        }
+        if arg.var.is_some() {
+            Self::add_property(out, "isVariadic", "true", level + 1)?;
+        }
+        if let Some(ref default) = arg.default {
+            Self::add_property(out, "default", &js_string(default), level + 1)?;
+        }
+
+        match arg.candidates {
+            // Fig can't hide an arg, so it's kept without suggesting anything
+            _ if arg.hidden => {}
+            Candidates::Choices(ref choices) => {
+                let choices: Vec<String> = choices.iter().map(|c| js_string(c)).collect();
+                Self::add_property(
+                    out,
+                    "suggestions",
+                    &format!("[{}]", choices.join(", ")),
+                    level + 1,
+                )?;
+            }
+            Candidates::Files => {
+                Self::add_property(out, "template", &js_string("filepaths"), level + 1)?;
+            }
+            Candidates::Dirs => {
+                Self::add_property(out, "template", &js_string("folders"), level + 1)?;
+            }
+            Candidates::Run(source_id) if self.completor.is_some() => {
+                let func_name =
+                    namespace::arg_complete_func_name(&self.graph.source(source_id).name);
+                Self::add_property(out, "generators", &func_name, level + 1)?;
+            }
+            _ => {}
+        }
+
+        Self::indent(out, level)?;
+        out.write_str("},\n")
+    }
+
+    /// Writes the generator constant of the source, see [`Self::collect_sources`].
+    fn write_generator(&self, out: &mut dyn Write, id: SourceId) -> fmt::Result {
+        let source = self.graph.source(id);
+        let func_name = namespace::arg_complete_func_name(&source.name);
+        let completor = self
+            .completor
+            .expect("No completor! Can't generate arg completions without it");
+        let encoded_script = mbase64::encode(&source.run);
+
+        // Fig can't tell a path from a word by itself, so only the fixed types are kept
+        let fig_type = match source.match_type {
+            Some(MatchType::File) => ", type: \"file\"",
+            Some(MatchType::Dir) => ", type: \"folder\"",
+            Some(MatchType::Arg) => ", type: \"arg\"",
+            Some(MatchType::Word | MatchType::Auto) | None => "",
+        };
+        let post_process = match source.descs {
+            false => format!("(line) => ({{ name: line{} }})", fig_type),
+            true => format!(
+                r#"(line) => {{
+        const [name, ...description] = line.split(":");
+        return {{ name, description: description.join(":"){} }};
+      }}"#,
+                fig_type
+            ),
+        };
+
+        writeln!(
+            out,
+            r#"const {}: Fig.Generator = {{
+  // {}
+  script: (tokens) => [
+    {},
+    "complete",
+    "--current",
+    String(tokens.length - 1),
+    "--line-hex",
+    _line_hex(tokens),
+    "--shell",
+    {},
+{}    "--",
+    {},
+  ],
+  postProcess: (out) =>
+    out
+      .split("\n")
+      .filter((line) => line.length > 0)
+      .map({}),
+}};
+"#,
+            func_name,
+            js_comment(&source.run, "\n  // "),
+            js_string(&completor.exe_path.display().to_string()),
+            js_string(&completor.shell.display().to_string()),
+            completor
+                .cache_args(source)
+                .iter()
+                .map(|arg| format!("    {},\n", js_string(arg)))
+                .collect::<String>(),
+            js_string(&encoded_script),
+            post_process,
+        )
+    }
+}
+
+/// Strips the required/optional brackets and variadic ellipsis from an arg, e.g. `<FILE>…`.
+fn arg_display_name(repr: &str) -> &str {
+    repr.trim_end_matches(['…', '.'])
+        .trim_start_matches(['<', '['])
+        .trim_end_matches(['>', ']'])
+}
+
+/// Continues a line comment over every line of the source, whichever JavaScript line
+/// terminator ends it, so that none of it can end up outside the comment.
+fn js_comment(source: &str, separator: &str) -> String {
+    source
+        .replace("\r\n", "\n")
+        .split(['\n', '\r', '\u{2028}', '\u{2029}'])
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Quotes a string as a double-quoted JavaScript string literal.
+fn js_string(source: &str) -> String {
+    let mut quoted = String::with_capacity(source.len() + 2);
+    quoted.push('"');
+    for c in source.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            '\t' => quoted += "\\t",
+            '\u{2028}' | '\u{2029}' => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_js_string() {
+        assert_eq!(js_string("plain"), r#""plain""#);
+        assert_eq!(js_string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(js_string(r"C:\path"), r#""C:\\path""#);
+        assert_eq!(js_string("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(js_string("\u{1b}[0m"), r#""\u001b[0m""#);
+    }
+
+    #[test]
+    fn test_js_comment() {
+        assert_eq!(js_comment("a", "\n// "), "a");
+        assert_eq!(js_comment("a\nb\r\nc", "\n// "), "a\n// b\n// c");
+        assert_eq!(
+            js_comment("a\rb\u{2028}c\u{2029}d", "\n// "),
+            "a\n// b\n// c\n// d"
+        );
+    }
+
+    #[test]
+    fn test_arg_display_name() {
+        assert_eq!(arg_display_name("<FILE>"), "FILE");
+        assert_eq!(arg_display_name("[FILE]"), "FILE");
+        assert_eq!(arg_display_name("<FILE>…"), "FILE");
+        assert_eq!(arg_display_name("[FILE]..."), "FILE");
+        assert_eq!(arg_display_name("FILE"), "FILE");
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        let mut spec = crate::usage::UsageSpec::default();
+        spec.info.bin = "bin".to_string();
+        let graph = CompletionGraph::build(&spec);
+        assert!(
+            FigBackend
+                .generate(&graph, &GenerateOptions::default())
+                .is_ok()
+        );
+
+        let options = GenerateOptions {
+            standalone: true,
+            ..Default::default()
+        };
+        let err = FigBackend.generate(&graph, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the fig target doesn't support embedding the helper modules"
+        );
+    }
+}
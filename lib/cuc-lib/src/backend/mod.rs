@@ -0,0 +1,416 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    io,
+    path::PathBuf,
+};
+
+use crate::{
+    graph::{CompletionGraph, Source},
+    usage::CachePolicy,
+};
+
+pub mod clink;
+pub mod fig;
+
+/// An output format rendering the completions from a [`CompletionGraph`].
+///
+/// Implement this to add a target to `cuc generate --target`, by registering it
+/// in a [`Registry`].
+pub trait CompletionBackend {
+    /// Unique name used to select the backend, e.g. `clink`.
+    fn name(&self) -> &'static str;
+
+    /// Short description, shown in the help of `--target`.
+    fn about(&self) -> &'static str;
+
+    fn generate(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+    ) -> Result<String, GenerateError>;
+
+    /// Writes the completions to the sink. By default, the whole output is generated first,
+    /// backends may override this to write it as they go.
+    fn write(
+        &self,
+        graph: &CompletionGraph,
+        options: &GenerateOptions,
+        out: &mut dyn Write,
+    ) -> Result<(), GenerateError> {
+        out.write_str(&self.generate(graph, options)?)?;
+        Ok(())
+    }
+
+    /// Generates the completions split into several files, e.g. a root script loading the
+    /// completions of each top-level cmd on demand.
+    fn generate_split(
+        &self,
+        _graph: &CompletionGraph,
+        _options: &GenerateOptions,
+    ) -> Result<Vec<OutputFile>, GenerateError> {
+        Err(GenerateError::Unsupported {
+            backend: self.name(),
+            feature: "splitting the output",
+        })
+    }
+}
+
+/// A file of the output, with its path relative to the output dir.
+#[derive(Debug, Clone)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+#[derive(Debug)]
+pub enum GenerateError {
+    /// Two distinct items of the spec are given the same name in the output.
+    NameCollision {
+        name: String,
+        first: String,
+        second: String,
+    },
+    /// Writing the output failed.
+    Io(io::Error),
+    /// The sink of the output failed.
+    Fmt(fmt::Error),
+    /// The backend doesn't support the requested feature.
+    Unsupported {
+        backend: &'static str,
+        feature: &'static str,
+    },
+}
+
+impl std::fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerateError::NameCollision {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "'{}' is generated for both {} and {}",
+                name, first, second
+            ),
+            GenerateError::Io(err) => write!(f, "failed to write the output: {}", err),
+            GenerateError::Fmt(err) => write!(f, "failed to write the output: {}", err),
+            GenerateError::Unsupported { backend, feature } => {
+                write!(f, "the {} target doesn't support {}", backend, feature)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GenerateError::Io(err) => Some(err),
+            GenerateError::Fmt(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<fmt::Error> for GenerateError {
+    fn from(err: fmt::Error) -> Self {
+        GenerateError::Fmt(err)
+    }
+}
+
+/// Writes the completions to an [`io::Write`] sink, see [`CompletionBackend::write`].
+pub fn write_io<W: io::Write>(
+    backend: &dyn CompletionBackend,
+    graph: &CompletionGraph,
+    options: &GenerateOptions,
+    out: W,
+) -> Result<(), GenerateError> {
+    let mut adapter = IoAdapter { out, error: None };
+    match backend.write(graph, options, &mut adapter) {
+        Err(GenerateError::Fmt(err)) => Err(adapter
+            .error
+            .map_or(GenerateError::Fmt(err), GenerateError::Io)),
+        Err(err) => Err(err),
+        Ok(()) => adapter.out.flush().map_err(GenerateError::Io),
+    }
+}
+
+/// Writes the UTF-8 to the [`io::Write`], keeping its error as [`fmt::Error`] can't hold it.
+struct IoAdapter<W> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Write for IoAdapter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// Checks that each name is generated for a single item, given as `(name, item)`.
+pub fn check_name_collisions<I>(names: I) -> Result<(), GenerateError>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut items: HashMap<String, String> = HashMap::new();
+    for (name, item) in names {
+        match items.get(&name) {
+            Some(first) if first != &item => {
+                return Err(GenerateError::NameCollision {
+                    name,
+                    first: first.clone(),
+                    second: item,
+                });
+            }
+            Some(_) => {}
+            None => {
+                items.insert(name, item);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The command used by the generated completions to resolve dynamic
+/// completions (`complete run=...`) through `cuc complete`.
+#[derive(Debug, Default, Clone)]
+pub struct Completor {
+    pub exe_path: PathBuf,
+    pub shell: PathBuf,
+    /// For how many seconds `cuc complete` reuses its output cached on disk, unless the spec of
+    /// the completion says otherwise. Not cached if none.
+    pub cache_ttl: Option<u64>,
+}
+
+impl Completor {
+    /// The args of `cuc complete` caching the output of the completion on disk, if it's cached.
+    pub fn cache_args(&self, source: &Source) -> Vec<String> {
+        let Some(ttl) = source.disk_cache.or(self.cache_ttl).filter(|ttl| *ttl > 0) else {
+            return vec![];
+        };
+        let mut args = vec!["--cache-ttl".to_string(), ttl.to_string()];
+        for name in source.cache_env.iter() {
+            args.push("--cache-env".into());
+            args.push(name.clone());
+        }
+        args
+    }
+}
+
+/// The options of the generation. Not every backend supports every option, a backend fails
+/// with [`GenerateError::Unsupported`] when one it doesn't support is set.
+#[derive(Debug, Default, Clone)]
+pub struct GenerateOptions {
+    /// Generate dynamic completions if present, else skip them.
+    pub completor: Option<Completor>,
+    /// Command names to register the completions for. Overrides the bin in the spec.
+    pub arg_matchers: Vec<String>,
+    /// Minify the output instead of pretty-printing it.
+    pub minify: bool,
+    /// Keep the generated functions out of the global scope, e.g. in a local module table, so
+    /// that the completions of many specs can be loaded together. The helpers they share are
+    /// kept out of it either way.
+    pub module: bool,
+    /// Embed the helper modules required by the output, so that it runs on its own.
+    pub standalone: bool,
+    /// Let the long flags taking an arg take it after an `=` too, e.g. `--out=FILE`,
+    /// unless their spec says otherwise.
+    pub opteq: bool,
+    /// Complete the default of an arg as its first match, so that completing an empty arg
+    /// fills it in.
+    pub suggest_defaults: bool,
+    /// List the cmds, flags and values in the order of the spec, or of the dynamic completions,
+    /// instead of sorting them, unless their spec says otherwise.
+    pub preserve_order: bool,
+    /// Run the dynamic completions in the background, filling them in once they're ready
+    /// instead of blocking the prompt, unless their spec says otherwise.
+    pub async_complete: bool,
+    /// How long the output of the dynamic completions is reused for the same inputs within
+    /// the shell, unless their spec says otherwise.
+    pub cache: CachePolicy,
+}
+
+pub struct Registry {
+    backends: Vec<Box<dyn CompletionBackend>>,
+}
+
+impl Registry {
+    /// A registry without any backend. See [`Registry::default`] for the builtin ones.
+    pub fn empty() -> Self {
+        Self { backends: vec![] }
+    }
+
+    /// Registers the backend, replacing any previous one with the same name.
+    pub fn register<B>(&mut self, backend: B)
+    where
+        B: CompletionBackend + 'static,
+    {
+        let backend: Box<dyn CompletionBackend> = Box::new(backend);
+        match self
+            .backends
+            .iter()
+            .position(|b| b.name() == backend.name())
+        {
+            Some(index) => self.backends[index] = backend,
+            None => self.backends.push(backend),
+        }
+    }
+
+    pub fn get<S>(&self, name: S) -> Option<&dyn CompletionBackend>
+    where
+        S: AsRef<str>,
+    {
+        self.backends
+            .iter()
+            .find(|b| b.name() == name.as_ref())
+            .map(|b| b.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn CompletionBackend> {
+        self.backends.iter().map(|b| b.as_ref())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(clink::ClinkBackend);
+        registry.register(fig::FigBackend);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyBackend {
+        name: &'static str,
+        about: &'static str,
+    }
+
+    impl CompletionBackend for DummyBackend {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn about(&self) -> &'static str {
+            self.about
+        }
+
+        fn generate(
+            &self,
+            _graph: &CompletionGraph,
+            _options: &GenerateOptions,
+        ) -> Result<String, GenerateError> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn test_default_backends() {
+        let registry = Registry::default();
+        let names: Vec<&str> = registry.iter().map(|b| b.name()).collect();
+        assert_eq!(names, vec!["clink", "fig"]);
+        assert!(registry.get("fig").is_some());
+        assert!(registry.get("zsh").is_none());
+    }
+
+    #[test]
+    fn test_split_is_unsupported_by_default() {
+        let graph = CompletionGraph::default();
+        let backend = DummyBackend {
+            name: "dummy",
+            about: "dummy",
+        };
+        let err = backend
+            .generate_split(&graph, &GenerateOptions::default())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GenerateError::Unsupported {
+                backend: "dummy",
+                ..
+            }
+        ));
+    }
+
+    /// Fails once more than `len` bytes are written.
+    struct ShortWriter {
+        len: usize,
+    }
+
+    impl io::Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.len {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "full"));
+            }
+            self.len -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_io() {
+        let mut spec = crate::usage::UsageSpec::default();
+        spec.info.bin = "bin".to_string();
+        let graph = CompletionGraph::build(&spec);
+        let options = GenerateOptions::default();
+        for backend in Registry::default().iter() {
+            let generated = backend.generate(&graph, &options).unwrap();
+            let mut written = vec![];
+            write_io(backend, &graph, &options, &mut written).unwrap();
+            assert_eq!(String::from_utf8(written).unwrap(), generated);
+
+            let short = ShortWriter { len: 10 };
+            match write_io(backend, &graph, &options, short) {
+                Err(GenerateError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::StorageFull),
+                result => panic!("expected an io error, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_register_replaces_same_name() {
+        let mut registry = Registry::default();
+        registry.register(DummyBackend {
+            name: "clink",
+            about: "dummy",
+        });
+        let names: Vec<&str> = registry.iter().map(|b| b.name()).collect();
+        assert_eq!(names, vec!["clink", "fig"]);
+        assert_eq!(registry.get("clink").unwrap().about(), "dummy");
+    }
+
+    #[test]
+    fn test_check_name_collisions() {
+        let names = |items: &[(&str, &str)]| {
+            let items: Vec<(String, String)> = items
+                .iter()
+                .map(|(name, item)| (name.to_string(), item.to_string()))
+                .collect();
+            check_name_collisions(items)
+        };
+        assert!(
+            names(&[
+                ("_cmd_a", "cmd a"),
+                ("_cmd_b", "cmd b"),
+                ("_cmd_a", "cmd a")
+            ])
+            .is_ok()
+        );
+        let err = names(&[("_cmd_a", "cmd a"), ("_cmd_a", "cmd b")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'_cmd_a' is generated for both cmd a and cmd b"
+        );
+    }
+}
@@ -0,0 +1,755 @@
+use std::collections::HashSet;
+
+use crate::{
+    namespace::NameSpace,
+    usage::{Alias, Arg, CachePolicy, Cmd, CompleteKind, Flag, MatchType, UsageSpec},
+};
+
+/// A shell-agnostic view of the completions described by a [`UsageSpec`].
+///
+/// The graph is built once from the spec: global flags are resolved to the scope
+/// declaring them, aliases are expanded into words and hidden items are filtered
+/// out (see [`Hidden`]). Every node gets a resolved identity, so the backends only have to render it.
+#[derive(Debug, Default, Clone)]
+pub struct CompletionGraph {
+    pub bin: String,
+    pub matchers: Vec<Matcher>,
+    pub flags: Vec<FlagNode>,
+    pub flag_tables: Vec<FlagTable>,
+    pub sources: Vec<Source>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MatcherId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlagId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlagTableId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceId(usize);
+
+/// A word to be matched, i.e. the name of a cmd or flag, or one of their aliases.
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub name: String,
+    pub alias: bool,
+    /// Hidden by the spec and kept by [`Hidden::Hide`], i.e. to be matched but not listed.
+    pub hidden: bool,
+}
+
+/// The completions of the root or a cmd.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    /// Path of cmd names from the root, e.g. `plugins::install`.
+    pub ns: NameSpace,
+    /// The cmd name followed by its aliases. Empty for the root.
+    pub words: Vec<Word>,
+    pub help: String,
+    /// Flags declared by this cmd, excluding the global ones.
+    pub flags: Vec<FlagId>,
+    /// Global flags declared by this cmd, shared with all of its descendants.
+    pub global_flags: Option<FlagTableId>,
+    /// Global flags declared by the ancestors, from the root down to the parent.
+    pub inherited_flags: Vec<FlagTableId>,
+    pub positions: Vec<Position>,
+    /// Subcmds, completed along with the first position.
+    pub subcmds: Vec<MatcherId>,
+    /// Whether the subcmds, flags and values are listed in the spec order, if set by the spec.
+    pub nosort: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlagNode {
+    /// Scope declaring the flag.
+    pub ns: NameSpace,
+    /// Identifier of the flag within its scope, the longest flag name without its dashes.
+    pub name: String,
+    /// The flag names followed by its aliases.
+    pub words: Vec<Word>,
+    pub help: String,
+    pub arg: Option<Position>,
+    /// Whether it can be used more than once.
+    pub repeatable: bool,
+    pub required: bool,
+    /// Whether the long names take the arg after an `=` too, if set by the spec.
+    pub opteq: Option<bool>,
+}
+
+/// Global flags declared at a scope.
+#[derive(Debug, Clone)]
+pub struct FlagTable {
+    pub ns: NameSpace,
+    pub flags: Vec<FlagId>,
+    /// Whether some of the words of the scope's table are shadowed by a nearer scope, this one
+    /// being derived from it with the flags and words left.
+    pub shadowed: bool,
+}
+
+/// An argument position, of a cmd or of a flag.
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub name: String,
+    pub repr: String,
+    pub required: bool,
+    /// Minimum and maximum (negative for unbounded) count of values if variadic.
+    pub var: Option<(i128, i128)>,
+    pub default: Option<String>,
+    pub candidates: Candidates,
+    /// Hidden by the spec and kept by [`Hidden::Hide`], i.e. its candidates aren't listed.
+    pub hidden: bool,
+    /// Whether the candidates are listed in their order, if set by the spec.
+    pub nosort: Option<bool>,
+}
+
+/// How the cmds, flags, args and aliases hidden by the spec are completed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Hidden {
+    /// Left out, as if they weren't in the spec. The args keep their position though, without
+    /// any candidates.
+    #[default]
+    Omit,
+    /// Kept but marked hidden, so that they're matched (e.g. coloured) but not listed.
+    Hide,
+    /// Completed like any other.
+    Show,
+}
+
+impl Hidden {
+    /// Whether the item is kept, along with whether it's marked hidden.
+    fn resolve(self, hide: bool) -> Option<bool> {
+        match (self, hide) {
+            (_, false) | (Hidden::Show, true) => Some(false),
+            (Hidden::Omit, true) => None,
+            (Hidden::Hide, true) => Some(true),
+        }
+    }
+}
+
+impl std::str::FromStr for Hidden {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "omit" => Ok(Hidden::Omit),
+            "hide" => Ok(Hidden::Hide),
+            "show" => Ok(Hidden::Show),
+            _ => Err(format!(
+                "invalid hidden policy '{}', expected omit, hide or show",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub enum Candidates {
+    #[default]
+    None,
+    Choices(Vec<String>),
+    Files,
+    Dirs,
+    Run(SourceId),
+}
+
+/// A dynamic completion from a `complete` node, shared by all the positions using it.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub name: String,
+    pub run: String,
+    pub descs: bool,
+    /// Added as typed matches if set, else as plain strings.
+    pub match_type: Option<MatchType>,
+    /// Whether it's run in the background, if set by the spec.
+    pub run_async: Option<bool>,
+    /// How long its output is reused, if set by the spec.
+    pub cache: Option<CachePolicy>,
+    /// For how many seconds its output is cached on disk by `cuc complete`, if set by the spec.
+    pub disk_cache: Option<u64>,
+    /// The env vars its output depends on, cached on disk per value.
+    pub cache_env: Vec<String>,
+}
+
+impl CompletionGraph {
+    pub fn build(spec: &UsageSpec) -> Self {
+        Self::build_with(spec, Hidden::default())
+    }
+
+    /// Builds the graph, resolving the hidden items of the spec as given.
+    pub fn build_with(spec: &UsageSpec, hidden: Hidden) -> Self {
+        let mut graph = Self {
+            bin: spec.info.bin.clone(),
+            ..Default::default()
+        };
+        let root = Matcher {
+            ns: NameSpace::root(),
+            words: vec![],
+            help: String::new(),
+            flags: vec![],
+            global_flags: None,
+            inherited_flags: vec![],
+            positions: vec![],
+            subcmds: vec![],
+            nosort: None,
+        };
+        graph.add_matcher(spec, hidden, root, &spec.flags, &spec.args, &spec.cmds);
+        graph
+    }
+
+    pub fn root(&self) -> MatcherId {
+        MatcherId(0)
+    }
+
+    pub fn matcher(&self, id: MatcherId) -> &Matcher {
+        &self.matchers[id.0]
+    }
+
+    pub fn flag(&self, id: FlagId) -> &FlagNode {
+        &self.flags[id.0]
+    }
+
+    pub fn flag_table(&self, id: FlagTableId) -> &FlagTable {
+        &self.flag_tables[id.0]
+    }
+
+    pub fn source(&self, id: SourceId) -> &Source {
+        &self.sources[id.0]
+    }
+
+    /// Global flag tables in effect for the matcher, from the root down to itself.
+    pub fn global_flag_tables(&self, id: MatcherId) -> impl Iterator<Item = FlagTableId> + '_ {
+        let matcher = self.matcher(id);
+        matcher
+            .inherited_flags
+            .iter()
+            .copied()
+            .chain(matcher.global_flags)
+    }
+
+    fn add_matcher<C>(
+        &mut self,
+        spec: &UsageSpec,
+        hidden: Hidden,
+        mut matcher: Matcher,
+        flags: &[Flag],
+        args: &[Arg],
+        cmds: &[C],
+    ) -> MatcherId
+    where
+        C: AsRef<Cmd>,
+    {
+        let id = MatcherId(self.matchers.len());
+        let ns = matcher.ns.clone();
+
+        // A flag shadows the words of the flags declared before it by the same cmd, and those of
+        // the global flags of the ancestors. The descendants only get the latter shadowed by the
+        // global flags, as the other ones aren't in effect for them.
+        let mut words: HashSet<&str> = HashSet::new();
+        let mut global_words: HashSet<&str> = HashSet::new();
+        let mut declared = vec![];
+        for flag in flags.iter().rev() {
+            let kept: Vec<&str> = flag.words().filter(|w| !words.contains(w)).collect();
+            let shadowed = kept.len() < flag.words().count();
+            if !kept.is_empty() || !shadowed {
+                declared.push((flag, shadowed.then_some(kept)));
+            }
+            words.extend(flag.words());
+            if flag.global {
+                global_words.extend(flag.words());
+            }
+        }
+        let ancestor_tables = std::mem::take(&mut matcher.inherited_flags);
+        matcher.inherited_flags = self.shadow_tables(&ancestor_tables, &words);
+        let ancestor_tables = match global_words.len() == words.len() {
+            true => matcher.inherited_flags.clone(),
+            false => self.shadow_tables(&ancestor_tables, &global_words),
+        };
+
+        let mut global_flags = vec![];
+        for (flag, kept) in declared.into_iter().rev() {
+            let Some(flag_hidden) = hidden.resolve(flag.hide) else {
+                continue;
+            };
+            let flag_id = self.add_flag(spec, hidden, &ns, flag, flag_hidden);
+            if let Some(kept) = kept {
+                self.flags[flag_id.0]
+                    .words
+                    .retain(|w| kept.contains(&w.name.as_str()));
+            }
+            if flag.global {
+                global_flags.push(flag_id);
+            } else {
+                matcher.flags.push(flag_id);
+            }
+        }
+        if !global_flags.is_empty() {
+            let table_id = FlagTableId(self.flag_tables.len());
+            self.flag_tables.push(FlagTable {
+                ns: ns.clone(),
+                flags: global_flags,
+                shadowed: false,
+            });
+            matcher.global_flags = Some(table_id);
+        }
+
+        matcher.positions = args
+            .iter()
+            .map(|arg| self.add_position(spec, arg, hidden.resolve(arg.hide)))
+            .collect();
+
+        // Reserving the id before the subcmds, so that the root is always the first.
+        let inherited_flags: Vec<FlagTableId> = ancestor_tables
+            .into_iter()
+            .chain(matcher.global_flags)
+            .collect();
+        self.matchers.push(matcher);
+
+        let mut subcmds = vec![];
+        for cmd in cmds.iter().map(|c| c.as_ref()) {
+            let Some(cmd_hidden) = hidden.resolve(cmd.hide) else {
+                continue;
+            };
+            let mut words = vec![Word {
+                name: cmd.name.clone(),
+                alias: false,
+                hidden: cmd_hidden,
+            }];
+            words.extend(Self::alias_words(hidden, &cmd.aliases, cmd_hidden));
+            let submatcher = Matcher {
+                ns: ns.clone().join(&cmd.name),
+                words,
+                help: cmd.help.clone(),
+                flags: vec![],
+                global_flags: None,
+                inherited_flags: inherited_flags.clone(),
+                positions: vec![],
+                subcmds: vec![],
+                nosort: cmd.nosort,
+            };
+            let subcmd_id =
+                self.add_matcher(spec, hidden, submatcher, &cmd.flags, &cmd.args, &cmd.cmds);
+            subcmds.push(subcmd_id);
+        }
+        self.matchers[id.0].subcmds = subcmds;
+        id
+    }
+
+    /// The tables in effect once the words are shadowed. A table keeps its id unless some of
+    /// its words are shadowed, in which case it's derived with the flags and words left.
+    fn shadow_tables(&mut self, tables: &[FlagTableId], words: &HashSet<&str>) -> Vec<FlagTableId> {
+        if words.is_empty() {
+            return tables.to_vec();
+        }
+        let is_shadowed = |word: &Word| words.contains(word.name.as_str());
+        let mut effective = vec![];
+        for table_id in tables.iter().copied() {
+            let table = &self.flag_tables[table_id.0];
+            let is_flag_shadowed = |f: &FlagId| self.flags[f.0].words.iter().any(is_shadowed);
+            if !table.flags.iter().any(is_flag_shadowed) {
+                effective.push(table_id);
+                continue;
+            }
+            let (ns, flags) = (table.ns.clone(), table.flags.clone());
+            let mut kept = vec![];
+            for flag_id in flags {
+                let flag = &self.flags[flag_id.0];
+                if !flag.words.iter().any(is_shadowed) {
+                    kept.push(flag_id);
+                } else if !flag.words.iter().all(is_shadowed) {
+                    let mut flag = flag.clone();
+                    flag.words.retain(|w| !is_shadowed(w));
+                    kept.push(FlagId(self.flags.len()));
+                    self.flags.push(flag);
+                }
+            }
+            if !kept.is_empty() {
+                effective.push(FlagTableId(self.flag_tables.len()));
+                self.flag_tables.push(FlagTable {
+                    ns,
+                    flags: kept,
+                    shadowed: true,
+                });
+            }
+        }
+        effective
+    }
+
+    /// The words of the aliases which are kept, hidden along with the cmd or flag they're of.
+    fn alias_words(hidden: Hidden, aliases: &[Alias], parent_hidden: bool) -> Vec<Word> {
+        aliases
+            .iter()
+            .filter_map(|alias| {
+                let alias_hidden = hidden.resolve(alias.hide)?;
+                Some(Word {
+                    name: alias.name.clone(),
+                    alias: true,
+                    hidden: parent_hidden || alias_hidden,
+                })
+            })
+            .collect()
+    }
+
+    fn add_flag(
+        &mut self,
+        spec: &UsageSpec,
+        hidden: Hidden,
+        ns: &NameSpace,
+        flag: &Flag,
+        flag_hidden: bool,
+    ) -> FlagId {
+        // Flags declared as "--out <file>" keep their arg in names, unless an
+        // explicit arg or choices were given.
+        let (names, arg_reprs): (Vec<&String>, Vec<&String>) =
+            flag.names.iter().partition(|name| name.starts_with('-'));
+        let mut words: Vec<Word> = names
+            .into_iter()
+            .map(|name| Word {
+                name: name.clone(),
+                alias: false,
+                hidden: flag_hidden,
+            })
+            .collect();
+        // The first of the longest names, e.g. `dry-run` for `-n --dry-run`
+        let name = words
+            .iter()
+            .rev()
+            .max_by_key(|w| w.name.len())
+            .map(|w| w.name.trim_start_matches('-').to_string())
+            .unwrap_or_else(|| flag.name.clone());
+        words.extend(Self::alias_words(hidden, &flag.aliases, flag_hidden));
+
+        let implicit_arg = arg_reprs.first().map(|repr| Arg::from_repr(*repr));
+        let arg = flag
+            .arg
+            .as_ref()
+            .or(implicit_arg.as_ref())
+            .map(|arg| self.add_position(spec, arg, hidden.resolve(arg.hide)));
+        let id = FlagId(self.flags.len());
+        self.flags.push(FlagNode {
+            ns: ns.clone(),
+            name,
+            words,
+            help: flag.help.clone(),
+            arg,
+            repeatable: flag.var,
+            required: flag.required,
+            opteq: flag.opteq,
+        });
+        id
+    }
+
+    /// Adds the arg, hidden as [`Hidden::resolve`] says. An omitted arg keeps its position, so
+    /// that the args after it don't shift, but it's hidden without any candidates.
+    fn add_position(&mut self, spec: &UsageSpec, arg: &Arg, hidden: Option<bool>) -> Position {
+        let mut nosort = arg.nosort;
+        let candidates = if hidden.is_none() {
+            Candidates::None
+        } else if !arg.choices.is_empty() {
+            Candidates::Choices(arg.choices.clone())
+        } else if let Some(complete) = spec.completes.get(&arg.name.to_lowercase()) {
+            nosort = nosort.or(complete.nosort);
+            match complete.kind {
+                CompleteKind::None => Candidates::None,
+                CompleteKind::File => Candidates::Files,
+                CompleteKind::Dir => Candidates::Dirs,
+                CompleteKind::Run(ref run) => {
+                    let id = match self.sources.iter().position(|s| s.name == complete.name) {
+                        Some(index) => SourceId(index),
+                        None => {
+                            self.sources.push(Source {
+                                name: complete.name.clone(),
+                                run: run.clone(),
+                                descs: complete.descs,
+                                match_type: complete.match_type,
+                                run_async: complete.run_async,
+                                cache: complete.cache,
+                                disk_cache: complete.disk_cache,
+                                cache_env: complete.cache_env.clone(),
+                            });
+                            SourceId(self.sources.len() - 1)
+                        }
+                    };
+                    Candidates::Run(id)
+                }
+            }
+        } else {
+            Candidates::None
+        };
+
+        Position {
+            name: arg.name.clone(),
+            repr: arg.repr.clone(),
+            required: arg.required,
+            var: arg
+                .var
+                .then(|| (arg.min.unwrap_or(0), arg.max.unwrap_or(-1))),
+            default: arg.default.clone().filter(|d| !d.is_empty()),
+            candidates,
+            hidden: hidden.unwrap_or(true),
+            nosort,
+        }
+    }
+}
+
+impl From<&UsageSpec> for CompletionGraph {
+    fn from(value: &UsageSpec) -> Self {
+        Self::build(value)
+    }
+}
+
+impl Matcher {
+    pub fn is_root(&self) -> bool {
+        self.ns.is_root()
+    }
+
+    /// Whether there is anything to complete after this matcher's words.
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+            && self.global_flags.is_none()
+            && self.inherited_flags.is_empty()
+            && self.positions.is_empty()
+            && self.subcmds.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::{Usage, parse_usage};
+
+    fn spec(source: &str) -> UsageSpec {
+        let doc: kdl::KdlDocument = source.parse().unwrap();
+        let mut spec = UsageSpec::default();
+        for node in doc.nodes() {
+            match parse_usage(node).unwrap() {
+                Some(Usage::Flag(flag)) => spec.flags.push(flag),
+                Some(Usage::Arg(arg)) => spec.args.push(arg),
+                Some(Usage::Cmd(cmd)) => spec.cmds.push(cmd),
+                Some(Usage::Complete(complete)) => {
+                    spec.completes.insert(complete.name.clone(), complete);
+                }
+                None => {}
+            }
+        }
+        spec
+    }
+
+    fn words(words: &[Word]) -> Vec<&str> {
+        words.iter().map(|w| w.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_root_is_first() {
+        let graph = CompletionGraph::build(&spec("cmd a { cmd b }"));
+        assert!(graph.matcher(graph.root()).is_root());
+        assert_eq!(graph.matchers.len(), 3);
+    }
+
+    #[test]
+    fn test_global_flags_inherited_by_descendants() {
+        let graph = CompletionGraph::build(&spec(
+            r#"
+            flag "-v --verbose" global=#true
+            cmd a {
+                flag "--dry-run" global=#true
+                cmd b
+            }
+            "#,
+        ));
+        let root = graph.matcher(graph.root());
+        let a = graph.matcher(root.subcmds[0]);
+        let b = graph.matcher(a.subcmds[0]);
+        assert!(root.flags.is_empty());
+        assert_eq!(a.inherited_flags, vec![root.global_flags.unwrap()]);
+
+        let tables: Vec<FlagTableId> = graph.global_flag_tables(a.subcmds[0]).collect();
+        assert_eq!(
+            tables,
+            vec![root.global_flags.unwrap(), a.global_flags.unwrap()]
+        );
+        assert!(b.global_flags.is_none());
+        assert!(!b.is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_global_flags_not_inherited() {
+        let graph = CompletionGraph::build(&spec(
+            r#"
+            flag "-v --verbose" global=#true
+            cmd a {
+                flag "-v --verbose" help="Verbose a"
+                cmd b
+            }
+            "#,
+        ));
+        let root = graph.matcher(graph.root());
+        let a = graph.matcher(root.subcmds[0]);
+        let b = graph.matcher(a.subcmds[0]);
+        assert!(a.inherited_flags.is_empty());
+        assert_eq!(a.flags.len(), 1);
+        assert_eq!(b.inherited_flags, vec![root.global_flags.unwrap()]);
+    }
+
+    #[test]
+    fn test_global_flags_shadowed_per_word() {
+        let graph = CompletionGraph::build(&spec(
+            r#"
+            flag "-v --verbose" global=#true
+            cmd a {
+                flag "-v" help="Version a"
+                cmd b
+            }
+            cmd c {
+                flag "-v" global=#true
+                cmd d
+            }
+            "#,
+        ));
+        let words = |id: MatcherId| -> Vec<String> {
+            let matcher = graph.matcher(id);
+            let tables = graph.global_flag_tables(id).map(|t| graph.flag_table(t));
+            matcher
+                .flags
+                .iter()
+                .chain(tables.flat_map(|t| &t.flags))
+                .flat_map(|f| &graph.flag(*f).words)
+                .map(|w| w.name.clone())
+                .collect()
+        };
+        let root = graph.matcher(graph.root());
+        let a = root.subcmds[0];
+        let c = root.subcmds[1];
+        assert_eq!(words(a), vec!["-v", "--verbose"]);
+        // The other words of the flag are left in a table derived from the root's one
+        let table = graph.flag_table(graph.matcher(a).inherited_flags[0]);
+        assert!(table.shadowed);
+        assert_eq!(table.ns, NameSpace::root());
+        // Only shadowed for a, as its flag isn't global
+        let b = graph.matcher(a).subcmds[0];
+        assert_eq!(words(b), vec!["-v", "--verbose"]);
+        assert_eq!(
+            graph.matcher(b).inherited_flags,
+            vec![root.global_flags.unwrap()]
+        );
+
+        assert_eq!(words(c), vec!["--verbose", "-v"]);
+        let d = graph.matcher(c).subcmds[0];
+        assert_eq!(words(d), vec!["--verbose", "-v"]);
+        // Shadowed once for c, the derived table being shared with its descendants
+        let c = graph.matcher(c);
+        assert_eq!(
+            graph.matcher(d).inherited_flags,
+            vec![c.inherited_flags[0], c.global_flags.unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_hidden_are_filtered() {
+        let graph = CompletionGraph::build(&spec(
+            r#"
+            flag "--secret" hide=#true
+            cmd a {
+                cmd b hide=#true
+                arg "<x>" hide=#true
+            }
+            "#,
+        ));
+        let root = graph.matcher(graph.root());
+        let a = graph.matcher(root.subcmds[0]);
+        assert!(root.flags.is_empty());
+        assert!(a.subcmds.is_empty());
+        // The position is kept, so that the args after it don't shift
+        assert_eq!(a.positions.len(), 1);
+        assert!(a.positions[0].hidden);
+        assert!(matches!(a.positions[0].candidates, Candidates::None));
+    }
+
+    const HIDDEN: &str = r#"
+        flag "--secret" hide=#true
+        cmd a {
+            alias "b"
+            alias "c" hide=#true
+            cmd d hide=#true
+            arg "<x>" hide=#true
+        }
+        "#;
+
+    #[test]
+    fn test_hidden_aliases_are_filtered() {
+        let graph = CompletionGraph::build(&spec(HIDDEN));
+        let a = graph.matcher(graph.matcher(graph.root()).subcmds[0]);
+        assert_eq!(words(&a.words), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_hidden_kept_marked() {
+        let graph = CompletionGraph::build_with(&spec(HIDDEN), Hidden::Hide);
+        let root = graph.matcher(graph.root());
+        let a = graph.matcher(root.subcmds[0]);
+        let d = graph.matcher(a.subcmds[0]);
+        let hidden = |words: &[Word]| words.iter().map(|w| w.hidden).collect::<Vec<_>>();
+        assert_eq!(hidden(&graph.flag(root.flags[0]).words), vec![true]);
+        assert_eq!(words(&a.words), vec!["a", "b", "c"]);
+        assert_eq!(hidden(&a.words), vec![false, false, true]);
+        assert_eq!(hidden(&d.words), vec![true]);
+        assert!(a.positions[0].hidden);
+
+        let graph = CompletionGraph::build_with(&spec(HIDDEN), Hidden::Show);
+        let a = graph.matcher(graph.matcher(graph.root()).subcmds[0]);
+        assert_eq!(hidden(&a.words), vec![false, false, false]);
+        assert!(!a.positions[0].hidden);
+        assert_eq!(a.subcmds.len(), 1);
+    }
+
+    #[test]
+    fn test_words_and_implicit_flag_arg() {
+        let graph = CompletionGraph::build(&spec(
+            r#"
+            flag "-p --profile <name>" {
+                alias "--prof"
+            }
+            cmd install {
+                alias i
+            }
+            "#,
+        ));
+        let root = graph.matcher(graph.root());
+        let flag = graph.flag(root.flags[0]);
+        assert_eq!(words(&flag.words), vec!["-p", "--profile", "--prof"]);
+        assert!(flag.words[2].alias);
+        let arg = flag.arg.as_ref().unwrap();
+        assert_eq!(arg.name, "name");
+        assert!(arg.required);
+
+        let install = graph.matcher(root.subcmds[0]);
+        assert_eq!(words(&install.words), vec!["install", "i"]);
+        assert_eq!(install.ns.display(), "install");
+    }
+
+    #[test]
+    fn test_sources_are_shared() {
+        let graph = CompletionGraph::build(&spec(
+            r#"
+            arg "<tool>"
+            cmd use {
+                arg "<tool>" var=#true var_min=1
+            }
+            complete tool run="mise ls"
+            "#,
+        ));
+        assert_eq!(graph.sources.len(), 1);
+        let root = graph.matcher(graph.root());
+        let using = graph.matcher(root.subcmds[0]);
+        assert!(matches!(
+            root.positions[0].candidates,
+            Candidates::Run(SourceId(0))
+        ));
+        assert!(matches!(
+            using.positions[0].candidates,
+            Candidates::Run(SourceId(0))
+        ));
+        assert_eq!(using.positions[0].var, Some((1, -1)));
+    }
+}
@@ -1,2 +1,6 @@
+pub mod backend;
+pub mod graph;
+pub mod mbase64;
+pub mod mhex;
 pub mod namespace;
 pub mod usage;
@@ -0,0 +1,19 @@
+use base64::prelude::*;
+use std::io;
+
+pub fn encode<S>(source: S) -> String
+where
+    S: AsRef<str>,
+{
+    BASE64_STANDARD.encode(source.as_ref())
+}
+
+pub fn decode<S>(source: S) -> io::Result<String>
+where
+    S: AsRef<str>,
+{
+    let encoded = BASE64_STANDARD.decode(source.as_ref()).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("failed to decode: {e}"))
+    })?;
+    String::from_utf8(encoded).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,29 @@
+use std::io;
+
+pub fn encode<S>(source: S) -> String
+where
+    S: AsRef<[u8]>,
+{
+    source
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub fn decode<S>(source: S) -> io::Result<String>
+where
+    S: AsRef<str>,
+{
+    let source = source.as_ref();
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    if source.len() % 2 != 0 || !source.is_ascii() {
+        return Err(invalid(format!("failed to decode: invalid hex {source:?}")));
+    }
+    let decoded = (0..source.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&source[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(format!("failed to decode: {e}")))?;
+    String::from_utf8(decoded).map_err(|e| invalid(e.to_string()))
+}
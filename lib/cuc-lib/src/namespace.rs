@@ -1,18 +1,21 @@
 use std::fmt::Display;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NameSpace {
     scope: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NameSpaceView<'me> {
     scope: &'me [String],
 }
 
 impl NameSpace {
     const SEPARATOR: &'static str = "::";
-    const FUNC_SEPARATOR: &'static str = "_";
+    /// Separates the names of the scope in function names, as `_` is used for the dashes.
+    const FUNC_SEPARATOR: &'static str = "__";
+    /// Precedes the hash of the mangled names, never occurring in readable function names.
+    const HASH_SEPARATOR: &'static str = "___";
 
     pub fn root() -> Self {
         Self { scope: vec![] }
@@ -22,7 +25,7 @@ impl NameSpace {
         self.scope.is_empty()
     }
 
-    pub fn view(&self) -> NameSpaceView {
+    pub fn view(&self) -> NameSpaceView<'_> {
         NameSpaceView {
             scope: self.scope.as_slice(),
         }
@@ -39,7 +42,7 @@ impl NameSpace {
         self
     }
 
-    pub fn parent(&self) -> NameSpaceView {
+    pub fn parent(&self) -> NameSpaceView<'_> {
         if !self.scope.is_empty() {
             NameSpaceView {
                 scope: &self.scope[..self.scope.len() - 1],
@@ -49,6 +52,18 @@ impl NameSpace {
         }
     }
 
+    /// The last name in the scope, empty for the root.
+    pub fn name(&self) -> &str {
+        self.scope.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// The scopes from the root down to this one, e.g. the root, `a` and `a::b` for `a::b`.
+    pub fn scopes(&self) -> impl Iterator<Item = NameSpaceView<'_>> {
+        (0..=self.scope.len()).map(|len| NameSpaceView {
+            scope: &self.scope[..len],
+        })
+    }
+
     pub fn display(&self) -> String {
         self.scope.join(Self::SEPARATOR)
     }
@@ -73,7 +88,7 @@ impl NameSpaceView<'_> {
         self.scope.is_empty()
     }
 
-    pub fn parent(&self) -> NameSpaceView {
+    pub fn parent(&self) -> NameSpaceView<'_> {
         if !self.scope.is_empty() {
             NameSpaceView {
                 scope: &self.scope[..self.scope.len() - 1],
@@ -88,20 +103,30 @@ impl NameSpaceView<'_> {
         // assert_eq!(parent_scope("abc"), ""); // no underscores
     }
 
+    /// The last name in the scope, empty for the root.
+    pub fn name(&self) -> &str {
+        self.scope.last().map(String::as_str).unwrap_or_default()
+    }
+
     pub fn display(&self) -> String {
         self.scope.join(NameSpace::SEPARATOR)
     }
 
+    /// The scope mangled into a part of a Lua identifier, see [`mangle`].
     pub fn as_func_str(&self) -> String {
-        self.scope.join(NameSpace::FUNC_SEPARATOR)
+        mangle(self.scope)
     }
 
-    fn join_func_str(this: &mut String, other: impl AsRef<str>) {
-        let other = other.as_ref();
-        if !other.is_empty() {
-            *this += other;
-            *this += NameSpace::FUNC_SEPARATOR;
-        }
+    /// Mangles the scope followed by the name.
+    fn join_func_str(&self, name: &str) -> String {
+        let mut names = self.scope.to_vec();
+        names.push(name.to_string());
+        mangle(&names)
+    }
+
+    /// Name of the function returning the argmatcher of the cmd at this scope.
+    pub fn matcher_func_name(&self) -> String {
+        self.parent().cmd_func_name(self.name())
     }
 
     pub fn flag_func_name<S>(&self, name: S) -> String
@@ -109,8 +134,7 @@ impl NameSpaceView<'_> {
         S: AsRef<str>,
     {
         let mut func_name = String::from("_flag_");
-        Self::join_func_str(&mut func_name, self.as_func_str());
-        func_name += name.as_ref();
+        func_name += &self.join_func_str(name.as_ref());
         func_name
     }
 
@@ -120,13 +144,24 @@ impl NameSpaceView<'_> {
         func_name
     }
 
+    /// Name of the function returning the global flags of this scope left once some of their
+    /// words are shadowed, told apart by the words left.
+    pub fn shadowed_global_flag_func_name<S>(&self, words: &[S]) -> String
+    where
+        S: AsRef<str>,
+    {
+        let words: Vec<&str> = words.iter().map(|w| w.as_ref()).collect();
+        let mut func_name = String::from("_global_flags_");
+        func_name += &self.join_func_str(&words.join(" "));
+        func_name
+    }
+
     pub fn cmd_func_name<S>(&self, name: S) -> String
     where
         S: AsRef<str>,
     {
         let mut func_name = String::from("_cmd_");
-        Self::join_func_str(&mut func_name, self.as_func_str());
-        func_name += &name.as_ref();
+        func_name += &self.join_func_str(name.as_ref());
         func_name
     }
 }
@@ -143,15 +178,43 @@ impl Display for NameSpaceView<'_> {
     }
 }
 
-pub fn slugify<S>(input: S) -> String
+/// Mangles the names into a part of a Lua identifier, such that distinct names never collide.
+///
+/// Names made of ASCII alphanumerics joined by single dashes are kept readable: the dashes
+/// become `_` and the names are joined by `__`, e.g. `remote__dry_run`. Anything else is
+/// slugified and suffixed by `___` and a hash of the exact names, e.g. `café___1b2e37f0`.
+pub fn mangle<S>(names: &[S]) -> String
 where
     S: AsRef<str>,
 {
-    input
-        .as_ref()
-        .chars()
-        .filter(|c| c.is_alphanumeric() || c == &'_')
-        .collect()
+    let is_readable = |name: &str| {
+        name.split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+    };
+    let mangled: Vec<String> = names
+        .iter()
+        .map(|name| {
+            name.as_ref()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        })
+        .collect();
+    let mut mangled = mangled.join(NameSpace::FUNC_SEPARATOR);
+    if names.iter().all(|name| is_readable(name.as_ref())) {
+        return mangled;
+    }
+
+    // FNV-1a, as the hash must be stable across runs and releases
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for name in names.iter() {
+        let name = name.as_ref().as_bytes();
+        for byte in (name.len() as u64).to_le_bytes().iter().chain(name) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    mangled += NameSpace::HASH_SEPARATOR;
+    mangled += &format!("{:08x}", hash as u32);
+    mangled
 }
 
 pub fn arg_complete_func_name<S>(complete_name: S) -> String
@@ -159,7 +222,7 @@ where
     S: AsRef<str>,
 {
     let mut func_name = String::from("_complete_arg_");
-    func_name += &slugify(complete_name.as_ref());
+    func_name += &mangle(&[complete_name]);
     func_name
 }
 
@@ -170,7 +233,8 @@ mod tests {
     #[test]
     fn test_constants() {
         assert_eq!(NameSpace::SEPARATOR, "::");
-        assert_eq!(NameSpace::FUNC_SEPARATOR, "_");
+        assert_eq!(NameSpace::FUNC_SEPARATOR, "__");
+        assert_eq!(NameSpace::HASH_SEPARATOR, "___");
     }
 
     #[test]
@@ -200,6 +264,14 @@ mod tests {
         assert_eq!(root_view.scope, &[] as &[String]);
     }
 
+    #[test]
+    fn test_scopes() {
+        let ns = NameSpace::root().join("a").join("b");
+        let scopes: Vec<String> = ns.scopes().map(|s| s.display()).collect();
+        assert_eq!(scopes, vec!["", "a", "a::b"]);
+        assert_eq!(NameSpace::root().scopes().count(), 1);
+    }
+
     #[test]
     fn test_join_string() {
         let ns = NameSpace::root().join("test");
@@ -317,4 +389,68 @@ mod tests {
         let ns3 = NameSpace::root().join(" \t \n ");
         assert!(ns3.is_root());
     }
+
+    #[test]
+    fn test_func_names() {
+        let ns = NameSpace::root().join("remote").join("add");
+        assert_eq!(ns.view().matcher_func_name(), "_cmd_remote__add");
+        assert_eq!(
+            ns.view().flag_func_name("dry-run"),
+            "_flag_remote__add__dry_run"
+        );
+        assert_eq!(
+            ns.view().global_flag_func_name(),
+            "_global_flags_remote__add"
+        );
+        assert_eq!(
+            NameSpace::root().view().global_flag_func_name(),
+            "_global_flags_"
+        );
+        assert_eq!(
+            NameSpace::root()
+                .view()
+                .shadowed_global_flag_func_name(&["--verbose"]),
+            "_global_flags___verbose___f0c4c5d0"
+        );
+        assert_eq!(NameSpace::root().view().flag_func_name("v"), "_flag_v");
+        assert_eq!(arg_complete_func_name("task"), "_complete_arg_task");
+    }
+
+    #[test]
+    fn test_mangle_is_injective() {
+        let names: &[&[&str]] = &[
+            &["foo-bar"],
+            &["foobar"],
+            &["foo_bar"],
+            &["foo", "bar"],
+            &["foo--bar"],
+            &["foo-", "bar"],
+            &["-foo"],
+            &["café"],
+            &["cafe"],
+            &["caf_"],
+            &[""],
+            &["a b"],
+            &["a", "b c"],
+            &["a b", "c"],
+        ];
+        let mangled: Vec<String> = names.iter().map(|n| mangle(n)).collect();
+        for (i, a) in mangled.iter().enumerate() {
+            assert!(
+                crate::backend::clink::lua::is_name(&format!("_{}", a)),
+                "{}",
+                a
+            );
+            for (j, b) in mangled.iter().enumerate() {
+                assert!(
+                    i == j || a != b,
+                    "{:?} and {:?} collide",
+                    names[i],
+                    names[j]
+                );
+            }
+        }
+        assert_eq!(mangle(&["foo-bar"]), "foo_bar");
+        assert!(mangle(&["foo_bar"]).starts_with("foo_bar___"));
+    }
 }
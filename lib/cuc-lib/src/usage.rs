@@ -4,7 +4,7 @@ use std::{
     io,
 };
 
-use crate::namespace::NameSpace;
+use crate::namespace::{NameSpace, NameSpaceView};
 
 #[derive(Debug, Default, Clone)]
 pub struct UsageSpec {
@@ -35,25 +35,35 @@ pub struct Alias {
     pub hide: bool,
 }
 
-#[derive(Debug, Default, Clone)]
-pub enum GlobalFlag {
-    #[default]
-    None,
-    Itself,
-    Imposed(NameSpace),
-}
-
 #[derive(Debug, Default, Clone)]
 pub struct Flag {
     pub name: String,
     pub names: Vec<String>,
     pub help: String,
     pub hide: bool,
-    pub global: GlobalFlag,
+    /// Whether it's in effect for the descendants of its cmd too, see
+    /// [`UsageSpec::effective_flags`].
+    pub global: bool,
+    /// Whether it can be used more than once, e.g. `--include a --include b` or `-vvv`.
+    pub var: bool,
+    pub required: bool,
+    /// Whether its long names take the arg after an `=` too, e.g. `--out=FILE`.
+    /// Defaults to the generator's choice if unset.
+    pub opteq: Option<bool>,
     pub aliases: Vec<Alias>,
     pub arg: Option<Arg>,
 }
 
+/// A flag in effect for a cmd, see [`UsageSpec::effective_flags`].
+#[derive(Debug, Clone)]
+pub struct EffectiveFlag<'me> {
+    /// Scope declaring the flag, i.e. the cmd itself or an ancestor if global.
+    pub scope: NameSpaceView<'me>,
+    pub flag: &'me Flag,
+    /// The words of the flag which aren't shadowed by a nearer scope, see [`Flag::words`].
+    pub words: Vec<&'me str>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Arg {
     pub name: String,
@@ -65,6 +75,8 @@ pub struct Arg {
     pub min: Option<i128>,
     pub max: Option<i128>,
     pub default: Option<String>,
+    /// Whether its choices are listed in the spec order instead of sorted, if set.
+    pub nosort: Option<bool>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -72,6 +84,9 @@ pub struct Cmd {
     pub name: String,
     pub help: String,
     pub hide: bool,
+    /// Whether its subcmds, flags and the values of its args are listed in the spec order
+    /// instead of sorted, if set.
+    pub nosort: Option<bool>,
     pub args: Vec<Arg>,
     pub flags: Vec<Flag>,
     pub aliases: Vec<Alias>,
@@ -83,16 +98,88 @@ pub struct Complete {
     pub name: String,
     pub kind: CompleteKind,
     pub descs: bool,
+    /// Whether the completions are listed in the order they're output instead of sorted, if set.
+    pub nosort: Option<bool>,
+    /// The match type of the completions, if set, e.g. to append a path separator to the dirs.
+    pub match_type: Option<MatchType>,
+    /// Whether it's run in the background instead of blocking the prompt, if set.
+    pub run_async: Option<bool>,
+    /// How long its output is reused for the same inputs, if set.
+    pub cache: Option<CachePolicy>,
+    /// For how many seconds `cuc complete` reuses its output cached on disk, if set.
+    pub disk_cache: Option<u64>,
+    /// The env vars its output depends on, cached on disk per value.
+    pub cache_env: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 pub enum CompleteKind {
+    #[default]
     None,
     File,
     Dir,
     Run(String),
 }
 
+/// The type of the matches completed by a `complete` node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchType {
+    Word,
+    Arg,
+    File,
+    Dir,
+    /// Detected per match: a dir if it ends with a path separator or is an existing dir, a file
+    /// if it's an existing file, else a word.
+    Auto,
+}
+
+/// How long the output of a `complete` node is reused for the same inputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Run again every time.
+    #[default]
+    Off,
+    /// Reused until the shell exits.
+    Session,
+    /// Reused for that many seconds.
+    Ttl(u64),
+}
+
+impl std::str::FromStr for CachePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(CachePolicy::Off),
+            "session" => Ok(CachePolicy::Session),
+            _ => s.parse().map(CachePolicy::Ttl).map_err(|_| {
+                format!(
+                    "invalid cache policy '{}', expected off, session or a number of seconds",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl std::str::FromStr for MatchType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "word" => Ok(MatchType::Word),
+            "arg" => Ok(MatchType::Arg),
+            "file" => Ok(MatchType::File),
+            "dir" => Ok(MatchType::Dir),
+            "auto" => Ok(MatchType::Auto),
+            _ => Err(format!(
+                "invalid match type '{}', expected word, arg, file, dir or auto",
+                s
+            )),
+        }
+    }
+}
+
 pub fn parse_name(node: &KdlNode) -> Result<String, UError> {
     if node.name().value() != "name" {
         return Err(UError::InvalidNodeName(io::Error::new(
@@ -274,7 +361,10 @@ pub fn parse_flag(node: &KdlNode) -> Result<Flag, UError> {
             match iden_name.value() {
                 "help" => flag.help = entry.value().as_string().unwrap_or_default().to_string(),
                 "hide" => flag.hide = entry.value().as_bool().unwrap_or_default(),
-                "global" => flag.global = entry.value().as_bool().unwrap_or_default().into(),
+                "global" => flag.global = entry.value().as_bool().unwrap_or_default(),
+                "opteq" => flag.opteq = entry.value().as_bool(),
+                "required" => flag.required = entry.value().as_bool().unwrap_or_default(),
+                "var" | "count" => flag.var |= entry.value().as_bool().unwrap_or_default(),
                 "negate" => {
                     let negate_flag = entry.value().as_string().unwrap_or_default().to_string();
                     if !negate_flag.is_empty() {
@@ -293,12 +383,10 @@ pub fn parse_flag(node: &KdlNode) -> Result<Flag, UError> {
                 "alias" => flag.aliases = parse_alias(child_node)?,
                 "choices" => {
                     if let Some(arg_name) = flag.names.pop() {
-                        let mut arg = Arg::default();
-                        arg.name = arg_name;
-                        arg.choices = parse_choices(child_node)?;
-                        if arg.name.starts_with("<") {
-                            arg.required = true;
-                        }
+                        let arg = Arg {
+                            choices: parse_choices(child_node)?,
+                            ..Arg::from_repr(arg_name)
+                        };
                         flag.arg = Some(arg);
                     }
                 }
@@ -331,16 +419,7 @@ pub fn parse_arg(node: &KdlNode) -> Result<Arg, UError> {
                 })?
                 .to_string();
 
-            if entry_arg_name.starts_with("<") {
-                arg.required = true;
-                let end = entry_arg_name.find(">").unwrap_or(entry_arg_name.len());
-                arg.name = entry_arg_name[1..end].to_string();
-            } else if entry_arg_name.starts_with("[") {
-                arg.required = false;
-                let end = entry_arg_name.find("]").unwrap_or(entry_arg_name.len());
-                arg.name = entry_arg_name[1..end].to_string();
-            }
-            arg.repr = entry_arg_name;
+            arg = Arg::from_repr(entry_arg_name);
         }
 
         if let Some(iden_name) = entry.name() {
@@ -350,6 +429,7 @@ pub fn parse_arg(node: &KdlNode) -> Result<Arg, UError> {
                 "var" => arg.var = entry.value().as_bool().unwrap_or_default(),
                 "var_max" => arg.max = entry.value().as_integer(),
                 "var_min" => arg.min = entry.value().as_integer(),
+                "nosort" => arg.nosort = entry.value().as_bool(),
                 _ => {}
             }
         }
@@ -366,7 +446,7 @@ pub fn parse_arg(node: &KdlNode) -> Result<Arg, UError> {
                     let choice = cn_entry
                         .value()
                         .as_string()
-                        .expect(format!("No choice found in {:?}", cn_entry).as_str())
+                        .unwrap_or_else(|| panic!("No choice found in {:?}", cn_entry))
                         .to_string();
                     choices.push(choice);
                 }
@@ -412,6 +492,7 @@ pub fn parse_cmd(node: &KdlNode) -> Result<Cmd, UError> {
                         .unwrap_or_default()
                 }
                 "hide" => cmd.hide = entry.value().as_bool().unwrap_or_default(),
+                "nosort" => cmd.nosort = entry.value().as_bool(),
                 _ => {}
             }
         }
@@ -470,6 +551,28 @@ pub fn parse_complete(node: &KdlNode) -> Result<Complete, UError> {
         if let Some(iden_name) = entry.name() {
             match iden_name.value() {
                 "descriptions" => complete.descs = entry.value().as_bool().unwrap_or_default(),
+                "nosort" => complete.nosort = entry.value().as_bool(),
+                "async" => complete.run_async = entry.value().as_bool(),
+                "cache" => {
+                    let value = entry.value();
+                    complete.cache = match (value.as_integer(), value.as_bool()) {
+                        (Some(secs), _) => u64::try_from(secs).ok().map(CachePolicy::Ttl),
+                        (_, Some(true)) => Some(CachePolicy::Session),
+                        (_, Some(false)) => Some(CachePolicy::Off),
+                        _ => value.as_string().and_then(|c| c.parse().ok()),
+                    };
+                }
+                "disk_cache" => {
+                    complete.disk_cache =
+                        entry.value().as_integer().and_then(|s| s.try_into().ok());
+                }
+                "cache_env" => {
+                    let names = entry.value().as_string().unwrap_or_default();
+                    complete.cache_env = names.split_whitespace().map(String::from).collect();
+                }
+                "match_type" => {
+                    complete.match_type = entry.value().as_string().and_then(|t| t.parse().ok());
+                }
                 "run" => {
                     let run = entry
                         .value()
@@ -480,9 +583,8 @@ pub fn parse_complete(node: &KdlNode) -> Result<Complete, UError> {
                 }
                 "type" => {
                     let arg_type = entry.value().as_string().unwrap_or_default();
-                    match arg_type {
-                        "file" => complete.kind = CompleteKind::File,
-                        _ => {}
+                    if arg_type == "file" {
+                        complete.kind = CompleteKind::File;
                     }
                 }
                 _ => {}
@@ -515,6 +617,12 @@ impl Complete {
             name: "file".to_string(),
             kind: CompleteKind::File,
             descs: false,
+            nosort: None,
+            match_type: None,
+            run_async: None,
+            cache: None,
+            disk_cache: None,
+            cache_env: vec![],
         }
     }
 
@@ -523,7 +631,47 @@ impl Complete {
             name: "file".to_string(),
             kind: CompleteKind::Dir,
             descs: false,
+            nosort: None,
+            match_type: None,
+            run_async: None,
+            cache: None,
+            disk_cache: None,
+            cache_env: vec![],
+        }
+    }
+}
+
+impl Arg {
+    /// Creates an arg from its representation, i.e. `<name>` if required, else `[name]`.
+    pub fn from_repr<S>(repr: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let repr = repr.into();
+        let mut arg = Arg::default();
+        if repr.starts_with("<") {
+            arg.required = true;
+            let end = repr.find(">").unwrap_or(repr.len());
+            arg.name = repr[1..end].to_string();
+        } else if repr.starts_with("[") {
+            arg.required = false;
+            let end = repr.find("]").unwrap_or(repr.len());
+            arg.name = repr[1..end].to_string();
+        } else {
+            arg.name = repr.clone();
         }
+        arg.repr = repr;
+        arg
+    }
+}
+
+impl Flag {
+    /// The words matching the flag on the line, i.e. its names without the arg and its aliases.
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        let names = self.names.iter().filter(|name| name.starts_with('-'));
+        names
+            .chain(self.aliases.iter().map(|alias| &alias.name))
+            .map(String::as_str)
     }
 }
 
@@ -561,17 +709,11 @@ impl Eq for Complete {}
 
 impl CompleteKind {
     pub fn is_none(&self) -> bool {
-        match self {
-            Self::None => true,
-            _ => false,
-        }
+        matches!(self, Self::None)
     }
 
     pub fn is_file(&self) -> bool {
-        match self {
-            Self::File => true,
-            _ => false,
-        }
+        matches!(self, Self::File)
     }
 
     pub fn run(&self) -> Option<&String> {
@@ -614,40 +756,153 @@ impl AsRef<Cmd> for Cmd {
     }
 }
 
-impl Default for CompleteKind {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
-impl From<bool> for GlobalFlag {
-    fn from(value: bool) -> Self {
-        match value {
-            true => Self::Itself,
-            false => Self::None,
+impl UsageSpec {
+    /// The cmd at the scope, none for the root or if there's no such cmd.
+    pub fn cmd(&self, ns: &NameSpace) -> Option<&Cmd> {
+        self.cmd_path(ns)?.pop()
+    }
+
+    /// The flags in effect for the cmd at the scope, or the root. That is, the global flags
+    /// declared by its ancestors from the root down, followed by the flags it declares.
+    ///
+    /// Global flags are kept once at the scope declaring them, instead of being copied into
+    /// every descendant. A word declared again by a nearer scope shadows the one of the farther
+    /// flag, which is left out once all of its words are. Empty if there's no cmd at the scope.
+    pub fn effective_flags<'me>(&'me self, ns: &'me NameSpace) -> Vec<EffectiveFlag<'me>> {
+        let Some(path) = self.cmd_path(ns) else {
+            return vec![];
+        };
+        let declared = std::iter::once(&self.flags).chain(path.iter().map(|cmd| &cmd.flags));
+        let mut effective: Vec<EffectiveFlag> = vec![];
+        for (depth, (scope, flags)) in ns.scopes().zip(declared).enumerate() {
+            let is_cmd = depth == path.len();
+            for flag in flags.iter().filter(|f| is_cmd || f.global) {
+                for e in effective.iter_mut() {
+                    e.words.retain(|word| !flag.words().any(|w| w == *word));
+                }
+                effective.retain(|e| !e.words.is_empty());
+                let words = flag.words().collect();
+                effective.push(EffectiveFlag { scope, flag, words });
+            }
         }
-    }
-}
-
-impl Flag {
-    pub fn is_global(&self) -> bool {
-        match self.global {
-            GlobalFlag::None => false,
-            _ => true,
+        effective
+    }
+
+    /// The cmds from the root down to the scope, excluding the root.
+    fn cmd_path(&self, ns: &NameSpace) -> Option<Vec<&Cmd>> {
+        let mut path: Vec<&Cmd> = vec![];
+        for scope in ns.scopes().skip(1) {
+            let cmd = match path.last() {
+                Some(parent) => parent
+                    .cmds
+                    .iter()
+                    .map(|cmd| cmd.as_ref())
+                    .find(|cmd| cmd.name == scope.name()),
+                None => self.cmds.iter().find(|cmd| cmd.name == scope.name()),
+            }?;
+            path.push(cmd);
         }
+        Some(path)
     }
+}
 
-    pub fn is_global_itself(&self) -> bool {
-        match self.global {
-            GlobalFlag::Itself => true,
-            _ => false,
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn is_global_imposed(&self) -> bool {
-        match self.global {
-            GlobalFlag::Imposed(_) => true,
-            _ => false,
+    fn spec(source: &str) -> UsageSpec {
+        let doc: kdl::KdlDocument = source.parse().unwrap();
+        let mut spec = UsageSpec::default();
+        for node in doc.nodes() {
+            match parse_usage(node).unwrap() {
+                Some(Usage::Flag(flag)) => spec.flags.push(flag),
+                Some(Usage::Cmd(cmd)) => spec.cmds.push(cmd),
+                _ => {}
+            }
         }
+        spec
+    }
+
+    fn effective(spec: &UsageSpec, ns: &NameSpace) -> Vec<(String, String)> {
+        spec.effective_flags(ns)
+            .iter()
+            .map(|e| (e.scope.display(), e.flag.names.join(" ")))
+            .collect()
+    }
+
+    #[test]
+    fn test_effective_flags() {
+        let spec = spec(
+            r#"
+            flag "-v --verbose" global=#true
+            flag "--version"
+            cmd a {
+                flag "-n --dry-run" global=#true
+                flag "--local"
+                cmd b {
+                    flag "--force"
+                }
+            }
+            "#,
+        );
+        let root = NameSpace::root();
+        assert_eq!(
+            effective(&spec, &root),
+            vec![
+                ("".into(), "-v --verbose".into()),
+                ("".into(), "--version".into())
+            ]
+        );
+        let b = root.clone().join("a").join("b");
+        assert_eq!(
+            effective(&spec, &b),
+            vec![
+                ("".into(), "-v --verbose".into()),
+                ("a".into(), "-n --dry-run".into()),
+                ("a::b".into(), "--force".into()),
+            ]
+        );
+        // Kept once, at the declaring scope
+        assert_eq!(spec.cmd(&b).unwrap().flags.len(), 1);
+        assert!(spec.effective_flags(&root.join("c")).is_empty());
+    }
+
+    #[test]
+    fn test_effective_flags_shadowed_by_nearer_scope() {
+        let spec = spec(
+            r#"
+            flag "-v --verbose" global=#true help="root"
+            cmd a {
+                flag "-v --verbose" help="a"
+            }
+            "#,
+        );
+        let a = NameSpace::root().join("a");
+        let flags = spec.effective_flags(&a);
+        assert_eq!(flags.len(), 1);
+        assert_eq!(flags[0].scope, a.view());
+        assert_eq!(flags[0].flag.help, "a");
+    }
+
+    #[test]
+    fn test_effective_flags_shadowed_per_word() {
+        let spec = spec(
+            r#"
+            flag "-v --verbose" global=#true
+            cmd a {
+                flag "-v" help="a"
+            }
+            "#,
+        );
+        let a = NameSpace::root().join("a");
+        let flags = spec.effective_flags(&a);
+        let words: Vec<(String, Vec<&str>)> = flags
+            .iter()
+            .map(|e| (e.scope.display(), e.words.clone()))
+            .collect();
+        assert_eq!(
+            words,
+            vec![("".into(), vec!["--verbose"]), ("a".into(), vec!["-v"])]
+        );
     }
 }